@@ -17,14 +17,35 @@
 use crate::{
 	error::{box_error, make_error, CfgError, CfgResult},
 	lexer::*,
-	Section,
+	name::is_valid_name,
+	ConflictPolicy, Key, KeyValue, MergeConflict, MergePolicy, ParseOptions, Schema, Section,
 };
-use std::{fmt::Display, fs, str::FromStr};
+use std::{
+	fmt::{Debug, Display},
+	fs,
+	path::Path,
+	str::FromStr,
+};
+
+/// A custom validation function run on a document by [`Document::validate_with`].
+pub type Validator = Box<dyn Fn(&Document) -> CfgResult<()>>;
+
+/// A handle to a [`Section`] within a [`Document`], obtained with [`Document::id_of`].
+///
+/// Unlike an index, a handle keeps referring to the same section when other sections are inserted
+/// or removed. It becomes invalid when its section is removed or the document is cleared, after
+/// which it resolves to [`None`]. Handles are only meaningful to the document that created them
+/// and its clones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SectionId(usize);
 
 /// A cfg document containing a collection of [`Section`]s.
+#[derive(Clone)]
 pub struct Document
 {
 	m_sections: Vec<Section>,
+	m_ids: Vec<usize>,
+	m_next_id: usize,
 }
 impl Default for Document
 {
@@ -32,6 +53,8 @@ impl Default for Document
 	{
 		Self {
 			m_sections: Default::default(),
+			m_ids: Default::default(),
+			m_next_id: 0,
 		}
 	}
 }
@@ -88,32 +111,47 @@ impl FromStr for Document
 
 	fn from_str(s: &str) -> Result<Self, Self::Err>
 	{
-		let mut lexer = Lexer::new();
+		Self::from_str_with(s, &ParseOptions::default())
+	}
+}
+impl TryFrom<&str> for Document
+{
+	type Error = CfgError;
 
-		match lexer.parse_string(s)
-		{
-			Err(e) =>
-			{
-				return Err(make_error(&format!(
-					"Cannot parse string into tokens to create a document: {e}"
-				)))
-			}
-			_ =>
-			{}
-		};
+	fn try_from(value: &str) -> Result<Self, Self::Error> { Self::from_str(value) }
+}
+impl TryFrom<String> for Document
+{
+	type Error = CfgError;
 
-		match Document::from_lexer(&mut lexer)
+	fn try_from(value: String) -> Result<Self, Self::Error> { Self::from_str(&value) }
+}
+impl TryFrom<&Path> for Document
+{
+	type Error = CfgError;
+
+	fn try_from(value: &Path) -> Result<Self, Self::Error>
+	{
+		match Self::from_file(value)
 		{
-			Ok(k) => Ok(k),
-			Err(e) =>
-			{
-				return Err(make_error(&format!(
-					"Cannot parse document from string: {e}"
-				)))
-			}
+			Ok(d) => Ok(d),
+			Err(e) => Err(make_error(&e.to_string())),
 		}
 	}
 }
+impl Debug for Document
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+	{
+		f.debug_struct("Document")
+			.field("sections", &self.m_sections)
+			.finish()
+	}
+}
+impl PartialEq for Document
+{
+	fn eq(&self, other: &Self) -> bool { self.m_sections == other.m_sections }
+}
 impl Display for Document
 {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
@@ -138,27 +176,107 @@ impl Document
 	{
 		Self {
 			m_sections: sections.to_vec(),
+			m_ids: (0..sections.len()).collect(),
+			m_next_id: sections.len(),
+		}
+	}
+	/// Creates and returns a new Document parsed from a string using the given options.
+	pub fn from_str_with(s: &str, options: &ParseOptions) -> Result<Self, CfgError>
+	{
+		let mut lexer = Lexer::with_options(options.clone());
+
+		match lexer.parse_string(s)
+		{
+			Err(e) =>
+			{
+				return Err(make_error(&format!(
+					"Cannot parse string into tokens to create a document: {e}"
+				)))
+			}
+			_ =>
+			{}
+		};
+
+		match Document::from_lexer(&mut lexer)
+		{
+			Ok(k) => Ok(k),
+			Err(e) =>
+			{
+				return Err(make_error(&format!(
+					"Cannot parse document from string: {e}"
+				)))
+			}
 		}
 	}
 	/// Creates and returns a new Document loaded from a file.
-	pub fn from_file(path: &str) -> CfgResult<Self>
+	pub fn from_file(path: impl AsRef<Path>) -> CfgResult<Self>
+	{
+		Self::from_file_with(path, &ParseOptions::default())
+	}
+	/// Creates and returns a new Document loaded from a file using the given options.
+	pub fn from_file_with(path: impl AsRef<Path>, options: &ParseOptions) -> CfgResult<Self>
 	{
 		let filedata = match fs::read_to_string(path)
 		{
 			Ok(fd) => fd,
 			Err(e) => return Err(box_error(&format!("Cannot read document from file: {e}"))),
 		};
-		match Self::from_str(&filedata)
+		match Self::from_str_with(&filedata, options)
 		{
 			Ok(s) => Ok(s),
 			Err(e) => return Err(box_error(&format!("Cannot read document from file: {e}"))),
 		}
 	}
 
+	/// Returns the document as a string with as little whitespace as possible. Keys are only
+	/// separated by a newline where the previous value ends with a number, which would otherwise run
+	/// into the key's name. Comments and metadata are left out. Numbers are written with type
+	/// postfixes where needed so the result is parsed back to an equal document, apart from
+	/// comments and metadata.
+	///
+	/// Returns an error if the document is empty or contains an infinite or NaN floating point
+	/// number, as neither can be parsed back.
+	pub fn minify(&self) -> CfgResult<String>
+	{
+		if self.m_sections.is_empty()
+		{
+			return Err(box_error("Cannot minify an empty document."));
+		}
+
+		let mut out = String::new();
+
+		for section in &self.m_sections
+		{
+			out.push('[');
+			out.push_str(section.name());
+			out.push(']');
+
+			for key in section.iter()
+			{
+				if out.ends_with(|c: char| c.is_ascii_alphanumeric())
+				{
+					out.push('\n');
+				}
+
+				if let Err(e) = key.write_minified(&mut out)
+				{
+					return Err(box_error(&format!(
+						"Cannot minify section {}: {e}",
+						section.name()
+					)));
+				}
+			}
+		}
+
+		Ok(out)
+	}
+
 	/// Returns an iterator over the contained sections.
 	pub fn iter(&self) -> std::slice::Iter<'_, Section> { self.m_sections.iter() }
 	/// Returns a mutable iterator over the contained [`Section`]s.
 	pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Section> { self.m_sections.iter_mut() }
+	/// Returns a vector containing a reference to each of the contained [`Section`]s, in order.
+	pub fn sections(&self) -> Vec<&Section> { self.m_sections.iter().collect() }
 
 	/// If the document is empty, containing no sections.
 	pub fn is_empty(&self) -> bool { self.m_sections.is_empty() }
@@ -243,6 +361,8 @@ impl Document
 		}
 
 		self.m_sections.push(section);
+		self.m_ids.push(self.m_next_id);
+		self.m_next_id += 1;
 		true
 	}
 	/// Inserts a new section at the given index. Returns true on success or false if the section is
@@ -259,6 +379,8 @@ impl Document
 		}
 
 		self.m_sections.insert(index, section);
+		self.m_ids.insert(index, self.m_next_id);
+		self.m_next_id += 1;
 		true
 	}
 	/// Removes the section with the given name if it exists in the document and returns true;
@@ -282,7 +404,434 @@ impl Document
 		}
 
 		self.m_sections.remove(index);
+		self.m_ids.remove(index);
 	}
 	/// Clears the document, removing all sections.
-	pub fn clear(&mut self) { self.m_sections.clear(); }
+	pub fn clear(&mut self)
+	{
+		self.m_sections.clear();
+		self.m_ids.clear();
+	}
+
+	/// Returns [`Some`] containing a handle to the section with the given name if it exists in the
+	/// document, otherwise [`None`].
+	pub fn id_of(&self, section: &str) -> Option<SectionId>
+	{
+		self.index_of(section).map(|i| SectionId(self.m_ids[i]))
+	}
+	/// Returns [`Some`] containing the index of the section referred to by a handle, or [`None`]
+	/// if the handle is no longer valid.
+	pub fn index_of_id(&self, id: SectionId) -> Option<usize>
+	{
+		self.m_ids.iter().position(|i| *i == id.0)
+	}
+	/// Returns [`Some`] containing a reference to the section referred to by a handle, or
+	/// [`None`] if the handle is no longer valid.
+	pub fn get_by_id(&self, id: SectionId) -> Option<&Section>
+	{
+		self.index_of_id(id).map(|i| &self.m_sections[i])
+	}
+	/// Returns [`Some`] containing a mutable reference to the section referred to by a handle, or
+	/// [`None`] if the handle is no longer valid.
+	pub fn get_by_id_mut(&mut self, id: SectionId) -> Option<&mut Section>
+	{
+		self.index_of_id(id).map(|i| &mut self.m_sections[i])
+	}
+
+	/// Merges the sections and keys of `other` into the document. Sections and keys that the
+	/// document does not contain are added, and keys contained in both are resolved using
+	/// `policy`. Existing keys keep their names when their values are replaced. If the merge fails,
+	/// the document is left unchanged.
+	pub fn merge(&mut self, other: &Document, policy: &MergePolicy) -> CfgResult<()>
+	{
+		let mut merged = self.clone();
+		let conflicts = merged.merge_into(other, policy)?;
+
+		if policy.conflicts == ConflictPolicy::Error
+		{
+			if let Some(c) = conflicts.first()
+			{
+				return Err(box_error(&format!(
+					"Cannot merge documents: The key {} in section {} has conflicting values.",
+					c.key, c.section
+				)));
+			}
+		}
+
+		*self = merged;
+		Ok(())
+	}
+	/// Merges `other` into the document like [`merge`](Document::merge), but rather than failing on
+	/// the first conflicting key, merges everything it can and returns every conflict found.
+	/// Conflicting keys are resolved using `policy`, except that [`ConflictPolicy::Error`] keeps the
+	/// existing value. If the merge fails because a section or key is invalid, the document is left
+	/// unchanged.
+	pub fn merge_report(
+		&mut self,
+		other: &Document,
+		policy: &MergePolicy,
+	) -> CfgResult<Vec<MergeConflict>>
+	{
+		let mut merged = self.clone();
+		let conflicts = merged.merge_into(other, policy)?;
+
+		*self = merged;
+		Ok(conflicts)
+	}
+	fn merge_into(
+		&mut self,
+		other: &Document,
+		policy: &MergePolicy,
+	) -> CfgResult<Vec<MergeConflict>>
+	{
+		let mut conflicts = Vec::new();
+
+		for section in other.iter()
+		{
+			let existing = match self.get_mut(section.name())
+			{
+				Some(s) => s,
+				None =>
+				{
+					if !self.push(section.clone())
+					{
+						return Err(box_error(&format!(
+							"Cannot merge documents: The section {} is invalid.",
+							section.name()
+						)));
+					}
+
+					continue;
+				}
+			};
+
+			let section_name = existing.name().clone();
+
+			for key in section.iter()
+			{
+				let current = match existing.get_mut(key.name())
+				{
+					Some(k) => k,
+					None =>
+					{
+						if !existing.push(key.clone())
+						{
+							return Err(box_error(&format!(
+								"Cannot merge documents: The key {} in section {} is invalid.",
+								key.name(),
+								section.name()
+							)));
+						}
+
+						continue;
+					}
+				};
+
+				if policy.arrays.merge(&mut current.value, &key.value) || current.value == key.value
+				{
+					continue;
+				}
+
+				conflicts.push(MergeConflict {
+					section: section_name.clone(),
+					key: current.name().clone(),
+					existing: current.value.clone(),
+					incoming: key.value.clone(),
+				});
+
+				if policy.conflicts == ConflictPolicy::Replace
+				{
+					// The existing key keeps its name.
+					let name = current.name().clone();
+
+					*current = key.clone();
+					current.rename(&name);
+				}
+			}
+		}
+
+		Ok(conflicts)
+	}
+
+	/// Calls `f` on each value in the document, including the values within tuples and tables, in
+	/// order until it returns [`Some`]. Returns the first [`Some`] returned by `f`, or [`None`] if
+	/// it never returns one; no further values are visited once `f` returns [`Some`].
+	pub fn find_map_value_mut<R>(
+		&mut self,
+		mut f: impl FnMut(&mut KeyValue) -> Option<R>,
+	) -> Option<R>
+	{
+		self.m_sections
+			.iter_mut()
+			.flat_map(|s| s.iter_mut())
+			.find_map(|k| k.value.find_map_mut(&mut f))
+	}
+
+	/// Returns each key name that appears in more than one section, along with the names of the
+	/// sections containing it, in the order they are found. Key names are compared
+	/// case-insensitively and the casing of the first key found is returned.
+	pub fn cross_section_duplicates(&self) -> Vec<(String, Vec<String>)>
+	{
+		let mut found: Vec<(String, Vec<String>)> = Vec::new();
+
+		for section in &self.m_sections
+		{
+			for key in section.iter()
+			{
+				let klo = key.name().to_lowercase();
+
+				match found.iter_mut().find(|(n, _)| n.to_lowercase() == klo)
+				{
+					Some((_, sections)) =>
+					{
+						if sections.last() != Some(section.name())
+						{
+							sections.push(section.name().clone());
+						}
+					}
+					None => found.push((key.name().clone(), vec![section.name().clone()])),
+				}
+			}
+		}
+
+		found.retain(|(_, sections)| sections.len() > 1);
+		found
+	}
+
+	/// Checks the document for broken invariants, such as invalid, empty or duplicate section and
+	/// key names, including the names of keys within tables. Returns a description of each problem
+	/// found, or an empty vector if there are none. Unlike [`validate`](Document::validate), this
+	/// does not need a schema and is intended for debugging documents built or modified in code.
+	///
+	/// Typed arrays cannot hold values of different types, and a [`KeyValue::Tuple`] is allowed to,
+	/// so the types of list elements are not checked.
+	pub fn check_invariants(&self) -> Vec<String>
+	{
+		let mut problems = Vec::new();
+
+		for (i, section) in self.m_sections.iter().enumerate()
+		{
+			name_invariants(
+				"Section",
+				section.name(),
+				&format!("at index {i}"),
+				&mut problems,
+			);
+
+			if self.m_sections[..i]
+				.iter()
+				.any(|s| s.name().to_lowercase() == section.name().to_lowercase())
+			{
+				problems.push(format!("Section {} is a duplicate.", section.name()));
+			}
+
+			key_invariants(&section.keys(), section.name(), &mut problems);
+		}
+
+		if self.m_ids.len() != self.m_sections.len()
+		{
+			problems.push(format!(
+				"Document has {} sections but {} section ids.",
+				self.m_sections.len(),
+				self.m_ids.len()
+			));
+		}
+
+		problems
+	}
+	/// Validates the document against a schema, checking that required keys exist and that keys
+	/// have the expected types. Keys without a schema entry are checked for non-empty
+	/// [`KeyValue::Tuple`]s whose elements could be written as a typed array. Returns an error
+	/// describing every problem found, one per line.
+	pub fn validate(&self, schema: &Schema) -> CfgResult<()>
+	{
+		let mut errors: Vec<String> = Vec::new();
+
+		for section in &self.m_sections
+		{
+			for key in section.iter()
+			{
+				let covered = schema.iter().any(|e| {
+					e.section.to_lowercase() == section.name().to_lowercase()
+						&& e.key.to_lowercase() == key.name().to_lowercase()
+				});
+
+				if covered || !key.value.is_homogeneous()
+				{
+					continue;
+				}
+
+				let array = match &key.value
+				{
+					KeyValue::Tuple(t) => t.first().and_then(|v| v.value_type().array_type()),
+					_ => None,
+				};
+
+				if let Some(array) = array
+				{
+					errors.push(format!(
+						"Key {}.{} is a Tuple that should be written as {array}.",
+						section.name(),
+						key.name()
+					));
+				}
+			}
+		}
+
+		for entry in schema.iter()
+		{
+			match self.get(&entry.section).and_then(|s| s.get(&entry.key))
+			{
+				Some(key) =>
+				{
+					if let Some(e) = entry.check(&key.value)
+					{
+						errors.push(format!("Key {}.{} {e}.", entry.section, entry.key));
+					}
+				}
+				None =>
+				{
+					if entry.required
+					{
+						errors.push(format!(
+							"Missing required key {}.{}.",
+							entry.section, entry.key
+						));
+					}
+				}
+			}
+		}
+
+		if errors.is_empty()
+		{
+			Ok(())
+		}
+		else
+		{
+			Err(box_error(&format!(
+				"Document does not match schema:\n{}",
+				errors.join("\n")
+			)))
+		}
+	}
+	/// Adds the default value of each schema entry that has one to the document if it does not
+	/// contain the key, creating the section if needed. Existing keys are left unchanged.
+	///
+	/// Each default is checked against the type of its entry first. Returns an error describing
+	/// every default that does not match, one per line, without changing the document.
+	pub fn fill_defaults(&mut self, schema: &Schema) -> CfgResult<()>
+	{
+		let errors: Vec<String> = schema
+			.iter()
+			.filter_map(|entry| {
+				let e = entry.check(entry.default.as_ref()?)?;
+				Some(format!("Default of {}.{} {e}.", entry.section, entry.key))
+			})
+			.collect();
+
+		if !errors.is_empty()
+		{
+			return Err(box_error(&format!(
+				"Schema has invalid defaults:\n{}",
+				errors.join("\n")
+			)));
+		}
+
+		for entry in schema.iter()
+		{
+			let default = match &entry.default
+			{
+				Some(d) => d,
+				None => continue,
+			};
+
+			if !self.contains(&entry.section)
+			{
+				self.push(Section::new(&entry.section, &[]));
+			}
+			if let Some(section) = self.get_mut(&entry.section)
+			{
+				if !section.contains(&entry.key)
+				{
+					section.push(Key::new(&entry.key, default.clone()));
+				}
+			}
+		}
+
+		Ok(())
+	}
+	/// Runs each validator on the document. Returns an error containing the messages of every
+	/// failed validator, one per line, if any fail.
+	pub fn validate_with(&self, validators: &[Validator]) -> CfgResult<()>
+	{
+		let mut errors: Vec<String> = Vec::new();
+
+		for validator in validators
+		{
+			if let Err(e) = validator(self)
+			{
+				errors.push(e.to_string());
+			}
+		}
+
+		if errors.is_empty()
+		{
+			Ok(())
+		}
+		else
+		{
+			Err(box_error(&format!(
+				"Document failed validation:\n{}",
+				errors.join("\n")
+			)))
+		}
+	}
+}
+
+/// Adds a problem to `problems` if `name`, the name of the `kind` at `place`, is empty or invalid.
+fn name_invariants(kind: &str, name: &str, place: &str, problems: &mut Vec<String>)
+{
+	if name.is_empty()
+	{
+		problems.push(format!("{kind} {place} has an empty name."));
+	}
+	else if !is_valid_name(name)
+	{
+		problems.push(format!("{kind} {place} has the invalid name {name}."));
+	}
+}
+/// Adds a problem to `problems` for each invalid or duplicate key name in `keys`, including keys
+/// within their values. `path` is the location of the keys, used in the problem descriptions.
+fn key_invariants(keys: &[&Key], path: &str, problems: &mut Vec<String>)
+{
+	for (i, key) in keys.iter().enumerate()
+	{
+		name_invariants("Key", key.name(), &format!("{path}[{i}]"), problems);
+
+		if keys[..i]
+			.iter()
+			.any(|k| k.name().to_lowercase() == key.name().to_lowercase())
+		{
+			problems.push(format!("Key {path}.{} is a duplicate.", key.name()));
+		}
+
+		value_invariants(&key.value, &format!("{path}.{}", key.name()), problems);
+	}
+}
+/// Checks the names of any keys within tables contained in `value`, the value at `path`.
+fn value_invariants(value: &KeyValue, path: &str, problems: &mut Vec<String>)
+{
+	match value
+	{
+		KeyValue::Tuple(t) =>
+		{
+			for (i, v) in t.iter().enumerate()
+			{
+				value_invariants(v, &format!("{path}[{i}]"), problems);
+			}
+		}
+		KeyValue::Table(t) => key_invariants(&t.iter().collect::<Vec<_>>(), path, problems),
+		_ =>
+		{}
+	}
 }
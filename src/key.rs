@@ -14,20 +14,25 @@
 // You should have received a copy of the GNU General Public License along with this program.
 // If not, see <https://www.gnu.org/licenses/>.
 //
-use std::fmt::Display;
+use std::{
+	collections::BTreeMap,
+	fmt::{Debug, Display},
+};
 
 use crate::{
 	error::{box_error, CfgResult},
 	lexer::{FromLexer, Lexer},
 	name::{as_valid_name, is_valid_name},
-	KeyValue, Token,
+	KeyValue, Token, COMMENT_CHAR,
 };
 
 /// A key-value pair containing a string name and a [`KeyValue`]
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Key
 {
 	m_name: String,
+	m_metadata: BTreeMap<String, String>,
+	m_comment: Option<String>,
 
 	/// The value of the key.
 	pub value: KeyValue,
@@ -38,6 +43,8 @@ impl Default for Key
 	{
 		Self {
 			m_name: as_valid_name(Default::default(), '_'),
+			m_metadata: Default::default(),
+			m_comment: None,
 			value: Default::default(),
 		}
 	}
@@ -48,6 +55,16 @@ impl FromLexer for Key
 	where
 		Self: Sized,
 	{
+		let mut metadata: BTreeMap<String, String> = BTreeMap::new();
+
+		while let Some(Token::Directive(..)) = lexer.peek()
+		{
+			if let Some(Token::Directive(name, value)) = lexer.pop_front()
+			{
+				metadata.insert(name, value);
+			}
+		}
+
 		if lexer.len() < 3
 		{
 			return Err(box_error("Not enough tokens left to load Key."));
@@ -59,12 +76,12 @@ impl FromLexer for Key
 		}
 		else
 		{
-			return Err(box_error("Unexpected token. Expected Identifier."));
+			return Err(lexer.error_at_last("Unexpected token. Expected Identifier."));
 		};
 
 		if lexer.pop_front().unwrap() != Token::Equals
 		{
-			return Err(box_error("Unexpected token. Expected Equals."));
+			return Err(lexer.error_at_last("Unexpected token. Expected Equals."));
 		}
 
 		let val = match KeyValue::from_lexer(lexer)
@@ -75,13 +92,63 @@ impl FromLexer for Key
 				return Err(box_error(&format!("Failed parsing KeyValue: {e}")));
 			}
 		};
-		Ok(Self::new(&id, val))
+
+		let mut key = Self::new(&id, val);
+		key.m_metadata = metadata;
+		Ok(key)
+	}
+}
+impl Debug for Key
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+	{
+		let mut debug = f.debug_struct("Key");
+		debug.field("name", &self.m_name);
+
+		if !self.m_metadata.is_empty()
+		{
+			debug.field("metadata", &self.m_metadata);
+		}
+		if let Some(comment) = &self.m_comment
+		{
+			debug.field("comment", comment);
+		}
+
+		debug.field("value", &self.value).finish()
 	}
 }
 impl Display for Key
 {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
 	{
+		if let Some(comment) = &self.m_comment
+		{
+			for line in comment.lines()
+			{
+				// Escape lines that would be parsed as directives, keeping their indentation.
+				let text = line.trim_start();
+
+				if text.starts_with('@')
+				{
+					let indent = &line[..line.len() - text.len()];
+					writeln!(f, "{COMMENT_CHAR} {indent}\\{text}")?;
+				}
+				else
+				{
+					writeln!(f, "{COMMENT_CHAR} {line}")?;
+				}
+			}
+		}
+		for (name, value) in &self.m_metadata
+		{
+			// A line break would end the directive early, writing the rest as a key.
+			writeln!(
+				f,
+				"{COMMENT_CHAR} @{name}: {}",
+				value.replace(['\r', '\n'], " ")
+			)?;
+		}
+
 		write!(f, "{} = {}", &self.m_name, self.value)
 	}
 }
@@ -92,15 +159,78 @@ impl Key
 	{
 		Self {
 			m_name: as_valid_name(name, '_'),
+			m_metadata: BTreeMap::new(),
+			m_comment: None,
 			value,
 		}
 	}
 
+	/// Creates a new key without making its name valid, for testing invariant checks.
+	#[cfg(test)]
+	pub(crate) fn with_unchecked_name(name: &str, value: KeyValue) -> Self
+	{
+		Self {
+			m_name: String::from(name),
+			..Self::new("_", value)
+		}
+	}
+
 	/// Returns the name of the key.
 	pub fn name(&self) -> &String { &self.m_name }
 	/// Renames the key. The given name may be modified to be valid.
 	pub fn rename(&mut self, name: &str) { self.m_name = as_valid_name(name, '_'); }
 
+	/// Returns a reference to the metadata of the key, filled from directive comments when parsing
+	/// with [`ParseOptions::parse_directives`](crate::ParseOptions::parse_directives).
+	pub fn metadata(&self) -> &BTreeMap<String, String> { &self.m_metadata }
+	/// Sets the metadata entry `name` of the key to `value`, with surrounding whitespace removed.
+	/// Returns true on success, or false if `name` is not a valid name or `value` contains a line
+	/// break, as the entry could not be written as a directive comment.
+	pub fn set_metadata(&mut self, name: &str, value: &str) -> bool
+	{
+		if !is_valid_name(name) || value.contains(['\r', '\n'])
+		{
+			return false;
+		}
+
+		self.m_metadata
+			.insert(String::from(name), String::from(value.trim()));
+		true
+	}
+	/// Removes the metadata entry `name` from the key, returning its value if it existed.
+	pub fn remove_metadata(&mut self, name: &str) -> Option<String> { self.m_metadata.remove(name) }
+
+	/// Returns the key with the given comment, see [`set_comment`](Key::set_comment).
+	pub fn with_comment(mut self, comment: &str) -> Self
+	{
+		self.set_comment(Some(comment));
+		self
+	}
+	/// Returns [`Some`] containing the comment of the key, or [`None`] if it has no comment.
+	pub fn comment(&self) -> Option<&str> { self.m_comment.as_deref() }
+	/// Sets or removes the comment of the key. The comment is written on the lines above the key
+	/// when it is converted to a string, with each line starting with a comment character. Lines
+	/// starting with `@` are written as `\@` so they are not read back as directives. Comments are
+	/// not read back when parsing.
+	pub fn set_comment(&mut self, comment: Option<&str>)
+	{
+		self.m_comment = comment.map(String::from);
+	}
+
+	/// Writes the key to `out` as `name=value`, without its comment or metadata. See
+	/// [`Document::minify`](crate::Document::minify).
+	pub(crate) fn write_minified(&self, out: &mut String) -> CfgResult<()>
+	{
+		out.push_str(&self.m_name);
+		out.push('=');
+
+		match self.value.write_minified(out)
+		{
+			Ok(()) => Ok(()),
+			Err(e) => Err(box_error(&format!("Key {}: {e}", self.m_name))),
+		}
+	}
+
 	/// If the key is valid.
 	pub fn is_valid(&self) -> bool { is_valid_name(&self.m_name) }
 }
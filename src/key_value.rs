@@ -38,6 +38,57 @@ pub enum KeyValue
 	Tuple(Vec<KeyValue>),
 	Table(Vec<Key>),
 }
+/// The possible types of a [`KeyValue`], without their contents.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueType
+{
+	String,
+	Integer,
+	Unsigned,
+	Float,
+
+	StringArray,
+	IntegerArray,
+	UnsignedArray,
+	FloatArray,
+
+	Tuple,
+	Table,
+}
+impl Display for ValueType
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{self:?}") }
+}
+impl ValueType
+{
+	/// Returns [`Some`] containing the type of the elements of an array type, or [`None`] if the
+	/// type is not an array.
+	pub fn element_type(&self) -> Option<ValueType>
+	{
+		match self
+		{
+			ValueType::StringArray => Some(ValueType::String),
+			ValueType::IntegerArray => Some(ValueType::Integer),
+			ValueType::UnsignedArray => Some(ValueType::Unsigned),
+			ValueType::FloatArray => Some(ValueType::Float),
+			_ => None,
+		}
+	}
+	/// Returns [`Some`] containing the array type whose elements have this type, or [`None`] if
+	/// there is no such array type.
+	pub fn array_type(&self) -> Option<ValueType>
+	{
+		match self
+		{
+			ValueType::String => Some(ValueType::StringArray),
+			ValueType::Integer => Some(ValueType::IntegerArray),
+			ValueType::Unsigned => Some(ValueType::UnsignedArray),
+			ValueType::Float => Some(ValueType::FloatArray),
+			_ => None,
+		}
+	}
+}
+
 impl Default for KeyValue
 {
 	fn default() -> Self { Self::String(String::default()) }
@@ -97,7 +148,7 @@ impl FromLexer for KeyValue
 								{
 									if !ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected separator or close \
 											 bracket.",
 										));
@@ -110,7 +161,7 @@ impl FromLexer for KeyValue
 								{
 									if ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected string or close bracket.",
 										));
 									}
@@ -122,7 +173,12 @@ impl FromLexer for KeyValue
 									closed = true;
 									break;
 								}
-								_ => return Err(box_error(&format!("Unexpected token: {}.", t))),
+								_ =>
+								{
+									return Err(
+										lexer.error_at_last(&format!("Unexpected token: {t}."))
+									)
+								}
 							}
 						}
 
@@ -160,7 +216,7 @@ impl FromLexer for KeyValue
 								{
 									if !ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected separator or close \
 											 bracket.",
 										));
@@ -172,7 +228,7 @@ impl FromLexer for KeyValue
 								{
 									if ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected integer or close bracket.",
 										));
 									}
@@ -184,7 +240,12 @@ impl FromLexer for KeyValue
 									closed = true;
 									break;
 								}
-								_ => return Err(box_error("Unexpected token.")),
+								_ =>
+								{
+									return Err(
+										lexer.error_at_last(&format!("Unexpected token: {t}."))
+									)
+								}
 							}
 						}
 
@@ -222,7 +283,7 @@ impl FromLexer for KeyValue
 								{
 									if !ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected separator or close \
 											 bracket.",
 										));
@@ -234,7 +295,7 @@ impl FromLexer for KeyValue
 								{
 									if ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected unsigned integer or close \
 											 bracket.",
 										));
@@ -247,7 +308,12 @@ impl FromLexer for KeyValue
 									closed = true;
 									break;
 								}
-								_ => return Err(box_error("Unexpected token.")),
+								_ =>
+								{
+									return Err(
+										lexer.error_at_last(&format!("Unexpected token: {t}."))
+									)
+								}
 							}
 						}
 
@@ -285,7 +351,7 @@ impl FromLexer for KeyValue
 								{
 									if !ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected separator or close \
 											 bracket.",
 										));
@@ -297,7 +363,7 @@ impl FromLexer for KeyValue
 								{
 									if ready
 									{
-										return Err(box_error(
+										return Err(lexer.error_at_last(
 											"Unexpected token; expected float or close bracket.",
 										));
 									}
@@ -309,7 +375,12 @@ impl FromLexer for KeyValue
 									closed = true;
 									break;
 								}
-								_ => return Err(box_error("Unexpected token.")),
+								_ =>
+								{
+									return Err(
+										lexer.error_at_last(&format!("Unexpected token: {t}."))
+									)
+								}
 							}
 						}
 
@@ -325,12 +396,34 @@ impl FromLexer for KeyValue
 					Token::CloseBracket => Ok(Self::StringArray(vec![])),
 					_ =>
 					{
-						return Err(box_error(
-							"Unexpected token; expected value or close bracket.",
-						))
+						return Err(lexer
+							.error_at_last("Unexpected token; expected value or close bracket."))
 					}
 				}
 			}
+			Token::Identifier(t) if t.len() == 1 && lexer.check(|t| t == &Token::OpenBracket) =>
+			{
+				lexer.pop_front();
+
+				match t.as_str()
+				{
+					"i" | "I" => Ok(Self::IntegerArray(read_annotated_array(
+						lexer,
+						token_as_integer,
+					)?)),
+					"u" | "U" => Ok(Self::UnsignedArray(read_annotated_array(
+						lexer,
+						token_as_unsigned,
+					)?)),
+					"f" | "F" => Ok(Self::FloatArray(read_annotated_array(
+						lexer,
+						token_as_float,
+					)?)),
+					_ => Err(box_error(&format!(
+						"Unknown array type annotation: {t}. Expected i, u or f."
+					))),
+				}
+			}
 			Token::OpenParen =>
 			{
 				let mut result: Vec<KeyValue> = Vec::new();
@@ -357,10 +450,10 @@ impl FromLexer for KeyValue
 							continue;
 						}
 
-						return Err(box_error(&format!(
-							"Unexpected token: {}. Expected comma.",
-							lexer.pop_front().unwrap()
-						)));
+						let tok = lexer.pop_front().unwrap();
+
+						return Err(lexer
+							.error_at_last(&format!("Unexpected token: {tok}. Expected comma.")));
 					}
 
 					let key = KeyValue::from_lexer(lexer)?;
@@ -403,10 +496,10 @@ impl FromLexer for KeyValue
 							continue;
 						}
 
-						return Err(box_error(&format!(
-							"Unexpected token: {}. Expected comma.",
-							tok
-						)));
+						let tok = lexer.pop_front().unwrap();
+
+						return Err(lexer
+							.error_at_last(&format!("Unexpected token: {tok}. Expected comma.")));
 					}
 
 					let key = Key::from_lexer(lexer)?;
@@ -432,12 +525,164 @@ impl FromLexer for KeyValue
 					Ok(Self::Table(result))
 				}
 			}
-			_ => Err(box_error(
-				"Unable to load KeyValue from tokens, unexpected token found.",
-			)),
+			_ => Err(lexer.error_at_last(&format!(
+				"Unable to load KeyValue from tokens, unexpected token found: {token}."
+			))),
+		}
+	}
+}
+/// Returns `s` as a quoted JSON string, escaping characters where needed.
+fn json_string(s: &str) -> String
+{
+	let mut result = String::from("\"");
+
+	for c in s.chars()
+	{
+		match c
+		{
+			'"' => result.push_str("\\\""),
+			'\\' => result.push_str("\\\\"),
+			'\n' => result.push_str("\\n"),
+			'\r' => result.push_str("\\r"),
+			'\t' => result.push_str("\\t"),
+			c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+			c => result.push(c),
+		}
+	}
+
+	result.push('"');
+	result
+}
+/// Returns `f` as a JSON number, or `null` if it is infinite or NaN.
+fn json_float(f: f64) -> String
+{
+	if f.is_finite()
+	{
+		f.to_string()
+	}
+	else
+	{
+		String::from("null")
+	}
+}
+/// Joins JSON values into an array or object, using the opening and closing characters in
+/// `brackets`.
+fn json_list(values: impl Iterator<Item = String>, brackets: &str, pretty: bool) -> String
+{
+	let values: Vec<String> = values.collect();
+	let (open, close) = brackets.split_at(1);
+
+	if values.is_empty()
+	{
+		String::from(brackets)
+	}
+	else if pretty
+	{
+		let values: Vec<String> = values.iter().map(|v| indent(v, 1)).collect();
+		format!("{open}\n{}\n{close}", values.join(",\n"))
+	}
+	else
+	{
+		format!("{open}{}{close}", values.join(","))
+	}
+}
+/// Converts a number token to an integer, truncating floating point numbers.
+fn token_as_integer(token: &Token) -> CfgResult<i64>
+{
+	match token
+	{
+		Token::Integer(i) => Ok(*i),
+		Token::Unsigned(u) => match i64::try_from(*u)
+		{
+			Ok(i) => Ok(i),
+			Err(_) => Err(box_error(&format!(
+				"Unsigned integer {u} is too large for an Integer."
+			))),
+		},
+		Token::Float(f) => Ok(*f as i64),
+		_ => Err(box_error(&format!(
+			"Unexpected token: {token}. Expected number."
+		))),
+	}
+}
+/// Converts a number token to an unsigned integer, truncating floating point numbers.
+fn token_as_unsigned(token: &Token) -> CfgResult<u64>
+{
+	match token
+	{
+		Token::Integer(i) => match u64::try_from(*i)
+		{
+			Ok(u) => Ok(u),
+			Err(_) => Err(box_error(&format!("Integer {i} cannot be Unsigned."))),
+		},
+		Token::Unsigned(u) => Ok(*u),
+		Token::Float(f) if *f >= 0.0 => Ok(*f as u64),
+		Token::Float(f) => Err(box_error(&format!("Float {f} cannot be Unsigned."))),
+		_ => Err(box_error(&format!(
+			"Unexpected token: {token}. Expected number."
+		))),
+	}
+}
+/// Converts a number token to a floating point number.
+fn token_as_float(token: &Token) -> CfgResult<f64>
+{
+	match token
+	{
+		Token::Integer(i) => Ok(*i as f64),
+		Token::Unsigned(u) => Ok(*u as f64),
+		Token::Float(f) => Ok(*f),
+		_ => Err(box_error(&format!(
+			"Unexpected token: {token}. Expected number."
+		))),
+	}
+}
+/// Reads the elements of an array with a type annotation, such as `f[1, 2, 3]`, following the
+/// opening bracket. Each element is converted to the annotated type with `convert`.
+fn read_annotated_array<T>(
+	lexer: &mut Lexer,
+	convert: fn(&Token) -> CfgResult<T>,
+) -> CfgResult<Vec<T>>
+{
+	let mut result: Vec<T> = Vec::new();
+	let mut ready = true;
+
+	while let Some(t) = lexer.pop_front()
+	{
+		match &t
+		{
+			Token::CloseBracket => return Ok(result),
+			Token::Separator =>
+			{
+				if ready
+				{
+					return Err(
+						lexer.error_at_last("Unexpected token; expected number or close bracket.")
+					);
+				}
+
+				ready = true;
+			}
+			_ =>
+			{
+				if !ready
+				{
+					return Err(lexer
+						.error_at_last("Unexpected token; expected separator or close bracket."));
+				}
+
+				match convert(&t)
+				{
+					Ok(v) => result.push(v),
+					Err(e) => return Err(lexer.error_at_last(&e.to_string())),
+				}
+				ready = false;
+			}
 		}
 	}
+
+	Err(box_error("Annotated array missing closing square bracket."))
 }
+
 impl Display for KeyValue
 {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
@@ -471,7 +716,15 @@ impl Display for KeyValue
 			}
 			KeyValue::IntegerArray(a) =>
 			{
-				let mut result = writeln!(f, "[");
+				// An empty array without an annotation is parsed as a StringArray.
+				let mut result = if a.is_empty()
+				{
+					writeln!(f, "i[")
+				}
+				else
+				{
+					writeln!(f, "[")
+				};
 
 				if result.is_err()
 				{
@@ -492,7 +745,7 @@ impl Display for KeyValue
 			}
 			KeyValue::UnsignedArray(a) =>
 			{
-				let mut result = writeln!(f, "[");
+				let mut result = writeln!(f, "u[");
 
 				if result.is_err()
 				{
@@ -513,7 +766,7 @@ impl Display for KeyValue
 			}
 			KeyValue::FloatArray(a) =>
 			{
-				let mut result = writeln!(f, "[");
+				let mut result = writeln!(f, "f[");
 
 				if result.is_err()
 				{
@@ -577,3 +830,316 @@ impl Display for KeyValue
 		}
 	}
 }
+impl KeyValue
+{
+	/// Returns the type of the value.
+	pub fn value_type(&self) -> ValueType
+	{
+		match self
+		{
+			KeyValue::String(_) => ValueType::String,
+			KeyValue::Integer(_) => ValueType::Integer,
+			KeyValue::Unsigned(_) => ValueType::Unsigned,
+			KeyValue::Float(_) => ValueType::Float,
+			KeyValue::StringArray(_) => ValueType::StringArray,
+			KeyValue::IntegerArray(_) => ValueType::IntegerArray,
+			KeyValue::UnsignedArray(_) => ValueType::UnsignedArray,
+			KeyValue::FloatArray(_) => ValueType::FloatArray,
+			KeyValue::Tuple(_) => ValueType::Tuple,
+			KeyValue::Table(_) => ValueType::Table,
+		}
+	}
+	/// Returns [`Some`] containing a borrowed slice of the strings if the value is a
+	/// [`KeyValue::StringArray`], otherwise [`None`].
+	pub fn as_str_slice(&self) -> Option<&[String]>
+	{
+		match self
+		{
+			KeyValue::StringArray(a) => Some(a),
+			_ => None,
+		}
+	}
+	/// Returns [`Some`] containing an iterator over the strings if the value is a
+	/// [`KeyValue::StringArray`], otherwise [`None`].
+	pub fn as_str_iter(&self) -> Option<impl Iterator<Item = &str>>
+	{
+		self.as_str_slice().map(|a| a.iter().map(String::as_str))
+	}
+	/// Returns the value as JSON. Tables are written as objects, arrays and tuples as arrays, and
+	/// floating point numbers that are infinite or NaN as `null`. When `pretty` is true, each
+	/// element of an object or array is written on its own line, indented with tabs.
+	pub fn to_json_string(&self, pretty: bool) -> String
+	{
+		match self
+		{
+			KeyValue::String(s) => json_string(s),
+			KeyValue::Integer(i) => i.to_string(),
+			KeyValue::Unsigned(u) => u.to_string(),
+			KeyValue::Float(f) => json_float(*f),
+			KeyValue::StringArray(a) => json_list(a.iter().map(|s| json_string(s)), "[]", pretty),
+			KeyValue::IntegerArray(a) => json_list(a.iter().map(|i| i.to_string()), "[]", pretty),
+			KeyValue::UnsignedArray(a) => json_list(a.iter().map(|u| u.to_string()), "[]", pretty),
+			KeyValue::FloatArray(a) => json_list(a.iter().map(|f| json_float(*f)), "[]", pretty),
+			KeyValue::Tuple(t) =>
+			{
+				json_list(t.iter().map(|v| v.to_json_string(pretty)), "[]", pretty)
+			}
+			KeyValue::Table(t) =>
+			{
+				let separator = if pretty { ": " } else { ":" };
+
+				json_list(
+					t.iter().map(|k| {
+						json_string(k.name()) + separator + &k.value.to_json_string(pretty)
+					}),
+					"{}",
+					pretty,
+				)
+			}
+		}
+	}
+	/// Calls `f` on the value and then on each value it contains, in order, until `f` returns
+	/// [`Some`]. Returns the first [`Some`] returned by `f`, or [`None`] if it never returns one.
+	pub fn find_map_mut<R, F: FnMut(&mut KeyValue) -> Option<R>>(&mut self, f: &mut F)
+		-> Option<R>
+	{
+		if let Some(r) = f(self)
+		{
+			return Some(r);
+		}
+
+		match self
+		{
+			KeyValue::Tuple(t) => t.iter_mut().find_map(|v| v.find_map_mut(f)),
+			KeyValue::Table(t) => t.iter_mut().find_map(|k| k.value.find_map_mut(f)),
+			_ => None,
+		}
+	}
+	/// Writes the value to `out` without whitespace, with lists written on a single line and
+	/// numbers written with type postfixes so they are parsed back to the same type. Returns an
+	/// error if the value contains an infinite or NaN floating point number, which cannot be parsed.
+	pub(crate) fn write_minified(&self, out: &mut String) -> CfgResult<()>
+	{
+		fn list<T>(
+			out: &mut String,
+			open: &str,
+			items: &[T],
+			f: impl Fn(&mut String, &T) -> CfgResult<()>,
+		) -> CfgResult<()>
+		{
+			out.push_str(open);
+
+			for (i, item) in items.iter().enumerate()
+			{
+				if i > 0
+				{
+					out.push(',');
+				}
+
+				f(out, item)?;
+			}
+
+			out.push(match open.chars().last()
+			{
+				Some('(') => ')',
+				Some('{') => '}',
+				_ => ']',
+			});
+			Ok(())
+		}
+		fn float(out: &mut String, f: f64) -> CfgResult<()>
+		{
+			if !f.is_finite()
+			{
+				return Err(box_error(&format!(
+					"The floating point number {f} cannot be parsed."
+				)));
+			}
+
+			out.push_str(&f.to_string());
+			Ok(())
+		}
+
+		match self
+		{
+			KeyValue::String(s) => out.push_str(&format!("\"{s}\"")),
+			KeyValue::Integer(i) => out.push_str(&i.to_string()),
+			KeyValue::Unsigned(u) => out.push_str(&format!("{u}u")),
+			KeyValue::Float(f) =>
+			{
+				float(out, *f)?;
+				out.push('f');
+			}
+			KeyValue::StringArray(a) => list(out, "[", a, |o, s| {
+				o.push_str(&format!("\"{s}\""));
+				Ok(())
+			})?,
+			KeyValue::IntegerArray(a) =>
+			{
+				// An empty array without an annotation is parsed as a StringArray.
+				let open = if a.is_empty() { "i[" } else { "[" };
+				list(out, open, a, |o, i| {
+					o.push_str(&i.to_string());
+					Ok(())
+				})?
+			}
+			KeyValue::UnsignedArray(a) => list(out, "u[", a, |o, u| {
+				o.push_str(&u.to_string());
+				Ok(())
+			})?,
+			KeyValue::FloatArray(a) => list(out, "f[", a, |o, f| float(o, *f))?,
+			KeyValue::Tuple(t) => list(out, "(", t, |o, v| v.write_minified(o))?,
+			KeyValue::Table(t) => list(out, "{", t, |o, k| k.write_minified(o))?,
+		}
+
+		Ok(())
+	}
+	/// Returns true if the value is a numeric scalar within the inclusive range `min..=max`,
+	/// otherwise false.
+	pub fn in_range(&self, min: f64, max: f64) -> bool
+	{
+		match self
+		{
+			KeyValue::Integer(i) => (min..=max).contains(&(*i as f64)),
+			KeyValue::Unsigned(u) => (min..=max).contains(&(*u as f64)),
+			KeyValue::Float(f) => (min..=max).contains(f),
+			_ => false,
+		}
+	}
+	/// Clamps a numeric scalar value to the inclusive range `min..=max`. Integral values are clamped
+	/// to the nearest integers within the range. Other values are left unchanged.
+	///
+	/// The value is also left unchanged if the range is empty, which is the case when either bound
+	/// is NaN or `min` is greater than `max`. An integral value is left unchanged if the range
+	/// contains no value of its type, such as `0.2..=0.8` for any integer or a range with a negative
+	/// `max` for a [`KeyValue::Unsigned`].
+	pub fn clamp_numeric(&mut self, min: f64, max: f64)
+	{
+		if min.is_nan() || max.is_nan() || min > max
+		{
+			return;
+		}
+
+		let (low, high) = (min.ceil(), max.floor());
+
+		match self
+		{
+			KeyValue::Integer(i) =>
+			{
+				if low > high
+				{
+					return;
+				}
+
+				if (*i as f64) < min
+				{
+					*i = low as i64;
+				}
+				else if (*i as f64) > max
+				{
+					*i = high as i64;
+				}
+			}
+			KeyValue::Unsigned(u) =>
+			{
+				if low.max(0.0) > high
+				{
+					return;
+				}
+
+				if (*u as f64) < min
+				{
+					*u = low as u64;
+				}
+				else if (*u as f64) > max
+				{
+					*u = high as u64;
+				}
+			}
+			KeyValue::Float(f) =>
+			{
+				if *f < min
+				{
+					*f = min;
+				}
+				else if *f > max
+				{
+					*f = max;
+				}
+			}
+			_ =>
+			{}
+		}
+	}
+	/// Returns true if the value is a list whose elements all have the same type, otherwise false.
+	/// Arrays are always homogeneous; a [`KeyValue::Tuple`] is homogeneous if it is empty or all
+	/// of its elements have the same [`ValueType`].
+	pub fn is_homogeneous(&self) -> bool
+	{
+		match self
+		{
+			KeyValue::StringArray(_)
+			| KeyValue::IntegerArray(_)
+			| KeyValue::UnsignedArray(_)
+			| KeyValue::FloatArray(_) => true,
+			KeyValue::Tuple(t) => match t.first()
+			{
+				Some(first) => t.iter().all(|v| v.value_type() == first.value_type()),
+				None => true,
+			},
+			_ => false,
+		}
+	}
+
+	/// Returns [`Some`] containing a reference to the first key in the table with the given name,
+	/// or [`None`] if the value is not a [`KeyValue::Table`] or contains no such key.
+	pub fn table_get(&self, name: &str) -> Option<&Key>
+	{
+		match self
+		{
+			KeyValue::Table(t) =>
+			{
+				let name = name.to_lowercase();
+				t.iter().find(|k| k.name().to_lowercase() == name)
+			}
+			_ => None,
+		}
+	}
+	/// Returns [`Some`] containing a mutable reference to the first key in the table with the given
+	/// name, or [`None`] if the value is not a [`KeyValue::Table`] or contains no such key.
+	pub fn table_get_mut(&mut self, name: &str) -> Option<&mut Key>
+	{
+		match self
+		{
+			KeyValue::Table(t) =>
+			{
+				let name = name.to_lowercase();
+				t.iter_mut().find(|k| k.name().to_lowercase() == name)
+			}
+			_ => None,
+		}
+	}
+	/// Returns true if the value is a [`KeyValue::Table`] containing a key with the given name,
+	/// otherwise false.
+	pub fn table_contains(&self, name: &str) -> bool { self.table_get(name).is_some() }
+	/// Adds a new key to the end of the table. Returns true on success or false if the value is not
+	/// a [`KeyValue::Table`], the key is not valid or the table already contains a key with the
+	/// same name.
+	pub fn table_insert(&mut self, key: Key) -> bool
+	{
+		if !key.is_valid() || self.table_contains(key.name())
+		{
+			return false;
+		}
+
+		match self
+		{
+			KeyValue::Table(t) =>
+			{
+				t.push(key);
+				true
+			}
+			_ => false,
+		}
+	}
+}
@@ -1,12 +1,71 @@
 // lexer.rs //
 
-use std::{collections::VecDeque, fs};
+use std::{collections::VecDeque, fs, sync::Arc};
 
 use crate::{
-	error::{box_error, CfgResult},
-	Token, COMMENT_CHAR,
+	error::{box_error, CfgError, CfgResult},
+	name::is_valid_name,
+	ImplicitIntegerType, ParseOptions, Token, COMMENT_CHAR,
 };
 
+/// Indexed access to the characters of a string being parsed to tokens.
+trait CharSource
+{
+	/// The amount of characters in the source.
+	fn len(&self) -> usize;
+	/// Returns the character at `index`.
+	fn at(&self, index: usize) -> char;
+	/// Returns the byte offset of the character at `index`, or the length of the string in bytes
+	/// if `index` is equal to [`CharSource::len`].
+	fn offset(&self, index: usize) -> usize;
+}
+
+/// Character source for ASCII strings, where each byte is a character.
+struct AsciiSource<'a>
+{
+	bytes: &'a [u8],
+}
+impl CharSource for AsciiSource<'_>
+{
+	fn len(&self) -> usize { self.bytes.len() }
+	fn at(&self, index: usize) -> char { self.bytes[index] as char }
+	fn offset(&self, index: usize) -> usize { index }
+}
+
+/// Character source for UTF-8 strings, decoded ahead of time with their byte offsets.
+struct Utf8Source
+{
+	chars: Vec<(usize, char)>,
+	end: usize,
+}
+impl CharSource for Utf8Source
+{
+	fn len(&self) -> usize { self.chars.len() }
+	fn at(&self, index: usize) -> char { self.chars[index].1 }
+	fn offset(&self, index: usize) -> usize
+	{
+		if index < self.chars.len()
+		{
+			self.chars[index].0
+		}
+		else
+		{
+			self.end
+		}
+	}
+}
+
+/// The implicit integers of an array being parsed with
+/// [`ImplicitIntegerType::UnsignedIfNonNegative`].
+#[derive(Default)]
+struct ImplicitArray
+{
+	/// The indices of the tokens read as unsigned integers.
+	unsigned: Vec<usize>,
+	/// If the array contains a negative implicit integer, making them all signed.
+	signed: bool,
+}
+
 enum NumberType
 {
 	Integer,
@@ -14,65 +73,309 @@ enum NumberType
 	Float,
 }
 
+/// Returns the line and column, both starting from 1, of the character at byte `offset` in `s`.
+fn line_column(s: &str, offset: usize) -> (usize, usize)
+{
+	let before = &s[..offset];
+	let line = before.matches('\n').count() + 1;
+	let column = match before.rfind('\n')
+	{
+		Some(n) => before[n + 1..].chars().count() + 1,
+		None => before.chars().count() + 1,
+	};
+
+	(line, column)
+}
+/// Returns true if the character at `index` starts a number, either a digit or a decimal point
+/// followed by a digit.
+fn starts_number<S: CharSource>(src: &S, index: usize) -> bool
+{
+	if index >= src.len()
+	{
+		return false;
+	}
+
+	let c = src.at(index);
+
+	c.is_ascii_digit() || (c == '.' && index + 1 < src.len() && src.at(index + 1).is_ascii_digit())
+}
+
+/// The bytes at the start of data created with [`Lexer::to_bytes`], including the format version.
+const BYTES_HEADER: &[u8] = b"PCFGTOK1";
+
+const TAG_IDENTIFIER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_DIRECTIVE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_UNSIGNED: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+/// The tag of the first token in [`SIMPLE_TOKENS`]; the following tokens have consecutive tags.
+const TAG_SIMPLE: u8 = 6;
+
+/// Tokens that contain no data, encoded by their index.
+const SIMPLE_TOKENS: [Token; 13] = [
+	Token::Equals,
+	Token::Separator,
+	Token::Add,
+	Token::Subtract,
+	Token::Multiply,
+	Token::Divide,
+	Token::Modulo,
+	Token::OpenBracket,
+	Token::CloseBracket,
+	Token::OpenBrace,
+	Token::CloseBrace,
+	Token::OpenParen,
+	Token::CloseParen,
+];
+
+/// Reads values from the bytes of tokens encoded with [`Lexer::to_bytes`].
+struct ByteReader<'a>
+{
+	bytes: &'a [u8],
+}
+impl<'a> ByteReader<'a>
+{
+	/// Removes and returns the next `count` bytes, or [`None`] if there are not enough left.
+	fn take(&mut self, count: usize) -> Option<&'a [u8]>
+	{
+		if count > self.bytes.len()
+		{
+			return None;
+		}
+
+		let (taken, rest) = self.bytes.split_at(count);
+		self.bytes = rest;
+		Some(taken)
+	}
+	fn read_u64(&mut self) -> Option<u64>
+	{
+		Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+	}
+	fn read_string(&mut self) -> Option<String>
+	{
+		let len = usize::try_from(self.read_u64()?).ok()?;
+		String::from_utf8(self.take(len)?.to_vec()).ok()
+	}
+}
+
+/// Returns true if the character at `index` is preceded only by whitespace on its line.
+fn starts_line<S: CharSource>(src: &S, index: usize) -> bool
+{
+	let mut i = index;
+
+	while i > 0
+	{
+		i -= 1;
+
+		if src.at(i) == '\n'
+		{
+			return true;
+		}
+		if !src.at(i).is_whitespace()
+		{
+			return false;
+		}
+	}
+
+	true
+}
+/// Parses the text of a comment in the form `@name: value` to a [`Token::Directive`], returning
+/// [`None`] if the comment is not a directive.
+fn parse_directive(comment: &str) -> Option<Token>
+{
+	let (name, value) = comment.trim().strip_prefix('@')?.split_once(':')?;
+	let name = name.trim();
+
+	if !is_valid_name(name)
+	{
+		return None;
+	}
+
+	Some(Token::Directive(
+		String::from(name),
+		String::from(value.trim()),
+	))
+}
+
+/// Parses text to [`Token`]s to be consumed by types implementing [`FromLexer`].
+///
+/// Cloning a lexer copies its remaining tokens, allowing a parser to try loading from the clone and
+/// either continue with it or discard it, leaving the original unaffected. The text the tokens were
+/// parsed from, kept for [`Lexer::excerpt`], is shared between clones rather than copied.
+#[derive(Clone)]
 pub struct Lexer
 {
 	tokens: VecDeque<Token>,
+	spans: VecDeque<Option<Span>>,
+	last_span: Option<Span>,
+	options: ParseOptions,
 }
 
+/// The position of a token within the text it was parsed from, in bytes. The text is released once
+/// no spans refer to it.
+#[derive(Clone, Debug)]
+struct Span
+{
+	source: Arc<str>,
+	start: usize,
+	end: usize,
+}
+
+impl Default for Lexer
+{
+	fn default() -> Self { Self::new() }
+}
 impl Lexer
 {
-	pub fn new() -> Self
+	/// Creates a new empty lexer that parses with the default [`ParseOptions`].
+	pub fn new() -> Self { Self::with_options(ParseOptions::default()) }
+	/// Creates a new empty lexer that parses with the given options.
+	pub fn with_options(options: ParseOptions) -> Self
 	{
 		Self {
 			tokens: VecDeque::new(),
+			spans: VecDeque::new(),
+			last_span: None,
+			options,
 		}
 	}
 
+	/// Parses a string to tokens. Strings that only contain ASCII characters take a faster path that
+	/// indexes bytes directly.
 	pub fn parse_string(&mut self, s: &str) -> CfgResult<()>
 	{
-		let chars: Vec<char> = s.chars().collect();
-
-		let slen = s.len();
-
-		if chars.len() != slen
+		if s.is_ascii()
+		{
+			self.parse_ascii(s)
+		}
+		else
+		{
+			self.parse_utf8(s)
+		}
+	}
+	/// Parses an ASCII string to tokens, indexing its bytes directly. Fails if `s` contains any
+	/// non-ASCII characters.
+	pub(crate) fn parse_ascii(&mut self, s: &str) -> CfgResult<()>
+	{
+		if !s.is_ascii()
 		{
 			return Err(box_error(
-				"Unable to parse strings containing multi-byte characters to tokens.",
+				"Unable to parse strings containing non-ASCII characters as ASCII.",
 			));
 		}
 
+		self.parse_source(
+			s,
+			&AsciiSource {
+				bytes: s.as_bytes(),
+			},
+		)
+	}
+	/// Parses a UTF-8 string to tokens. Non-ASCII characters are only allowed within strings and
+	/// comments.
+	pub(crate) fn parse_utf8(&mut self, s: &str) -> CfgResult<()>
+	{
+		self.parse_source(
+			s,
+			&Utf8Source {
+				chars: s.char_indices().collect(),
+				end: s.len(),
+			},
+		)
+	}
+	fn parse_source<S: CharSource>(&mut self, s: &str, src: &S) -> CfgResult<()>
+	{
+		let slen = src.len();
 		let mut i = 0;
+		let mut start = 0;
+		// The open brackets, parentheses and braces, with the implicit integers of open arrays.
+		let mut open: Vec<Option<ImplicitArray>> = Vec::new();
+
+		let source: Arc<str> = Arc::from(s);
 
 		while i < slen
 		{
-			if chars[i].is_whitespace()
+			self.push_spans(&source, src.offset(start), src.offset(i));
+			start = i;
+
+			if src.at(i).is_whitespace()
 			{
 				i += 1;
 				continue;
 			}
-			if chars[i] == COMMENT_CHAR
+			if src.at(i) == COMMENT_CHAR
 			{
-				i = match s[i + 1..].find('\n')
+				let begin = i + 1;
+
+				while i < slen && src.at(i) != '\n'
 				{
-					Some(e) => e + i + 2,
-					None => slen,
-				};
+					i += 1;
+				}
+
+				// Directives are only kept on their own line where a key may follow, not within or
+				// after values.
+				if self.options.parse_directives
+					&& open.is_empty()
+					&& starts_line(src, begin - 1)
+					&& (self.tokens.is_empty() || !self.expects_value())
+				{
+					if let Some(d) = parse_directive(&s[src.offset(begin)..src.offset(i)])
+					{
+						self.tokens.push_back(d);
+					}
+				}
 
 				continue;
 			}
+			if src.at(i) == '/' && i + 1 < slen && src.at(i + 1) == '*'
+			{
+				// Block comments nest, so track how many are open.
+				let mut depth = 1usize;
+				i += 2;
 
-			let numdot = chars[i] == '.' && (i + 1) < slen && chars[i + 1].is_ascii_digit();
+				while i < slen && depth > 0
+				{
+					if src.at(i) == '/' && i + 1 < slen && src.at(i + 1) == '*'
+					{
+						depth += 1;
+						i += 2;
+					}
+					else if src.at(i) == '*' && i + 1 < slen && src.at(i + 1) == '/'
+					{
+						depth -= 1;
+						i += 2;
+					}
+					else
+					{
+						i += 1;
+					}
+				}
+
+				if depth > 0
+				{
+					return Err(box_error(&format!(
+						"Block comment has no ending, {depth} left unclosed."
+					)));
+				}
+
+				continue;
+			}
 
-			if numdot || chars[i].is_ascii_digit()
+			let negative = src.at(i) == '-' && self.expects_value() && starts_number(src, i + 1);
+			let start = if negative { i + 1 } else { i };
+
+			if starts_number(src, start)
 			{
+				let numdot = src.at(start) == '.';
 				let mut hasdot = numdot;
-				let mut end = i + 1;
+				let mut end = start + 1;
 
 				let mut numtype: Option<NumberType> = None;
 
 				while end < slen
 				{
-					if chars[end] == '.'
+					if src.at(end) == '.'
 					{
 						if hasdot
 						{
@@ -84,9 +387,9 @@ impl Lexer
 						continue;
 					}
 
-					if !chars[end].is_ascii_digit()
+					if !src.at(end).is_ascii_digit()
 					{
-						numtype = match chars[end]
+						numtype = match src.at(end)
 						{
 							'i' | 'I' => Some(NumberType::Integer),
 							'u' | 'U' => Some(NumberType::Unsigned),
@@ -101,6 +404,10 @@ impl Lexer
 				}
 
 				let inc = numtype.is_some();
+				let implicit_integer = numtype.is_none()
+					&& !hasdot && self.options.implicit_integer_type
+					== ImplicitIntegerType::UnsignedIfNonNegative;
+				let signed_array = matches!(open.last(), Some(Some(a)) if a.signed);
 
 				if numtype.is_none()
 				{
@@ -109,6 +416,10 @@ impl Lexer
 						{
 							NumberType::Float
 						}
+						else if implicit_integer && !negative && !signed_array
+						{
+							NumberType::Unsigned
+						}
 						else
 						{
 							NumberType::Integer
@@ -116,15 +427,22 @@ impl Lexer
 					);
 				}
 
-				let rstr = if numdot
+				let mut rstr = if negative
 				{
-					"0".to_owned() + &s[i..end]
+					String::from("-")
 				}
 				else
 				{
-					s[i..end].to_owned()
+					String::new()
 				};
 
+				if numdot
+				{
+					rstr.push('0');
+				}
+
+				rstr.push_str(&s[src.offset(start)..src.offset(end)]);
+
 				match numtype.unwrap()
 				{
 					NumberType::Integer =>
@@ -162,6 +480,11 @@ impl Lexer
 					}
 					NumberType::Unsigned =>
 					{
+						if negative
+						{
+							return Err(box_error("Unsigned integers cannot be negative."));
+						}
+
 						let r = {
 							if hasdot
 							{
@@ -208,6 +531,41 @@ impl Lexer
 					}
 				}
 
+				// Arrays cannot mix signed and unsigned integers, so a negative implicit integer
+				// makes the array's earlier implicit integers signed.
+				if implicit_integer
+				{
+					if let Some(Some(array)) = open.last_mut()
+					{
+						if negative && !array.signed
+						{
+							array.signed = true;
+
+							for index in array.unsigned.drain(..)
+							{
+								if let Token::Unsigned(u) = self.tokens[index]
+								{
+									self.tokens[index] = match i64::try_from(u)
+									{
+										Ok(i) => Token::Integer(i),
+										Err(_) =>
+										{
+											return Err(box_error(&format!(
+												"Integer {u} is too large for an array containing \
+												 negative integers."
+											)))
+										}
+									};
+								}
+							}
+						}
+						else if !array.signed
+						{
+							array.unsigned.push(self.tokens.len() - 1);
+						}
+					}
+				}
+
 				i = end;
 
 				if inc
@@ -217,15 +575,15 @@ impl Lexer
 
 				continue;
 			}
-			else if chars[i].is_ascii_alphabetic() || chars[i] == '_'
+			else if src.at(i).is_ascii_alphabetic() || src.at(i) == '_'
 			{
 				let mut end = i + 1;
 
 				while end < slen
 				{
-					if !chars[end].is_ascii_alphabetic()
-						&& !chars[end].is_ascii_alphanumeric()
-						&& chars[end] != '_'
+					if !src.at(end).is_ascii_alphabetic()
+						&& !src.at(end).is_ascii_alphanumeric()
+						&& src.at(end) != '_'
 					{
 						break;
 					}
@@ -233,102 +591,177 @@ impl Lexer
 					end += 1;
 				}
 
-				self.tokens
-					.push_back(Token::Identifier(String::from(&s[i..end])));
+				self.tokens.push_back(Token::Identifier(String::from(
+					&s[src.offset(i)..src.offset(end)],
+				)));
 				i = end;
 				continue;
 			}
-			else if chars[i] == '='
+			else if src.at(i) == '='
 			{
 				self.tokens.push_back(Token::Equals);
 			}
-			else if chars[i] == ','
+			else if src.at(i) == ','
 			{
 				self.tokens.push_back(Token::Separator);
 			}
-			else if chars[i] == '+'
+			else if src.at(i) == '+'
 			{
 				self.tokens.push_back(Token::Add);
 			}
-			else if chars[i] == '-'
+			else if src.at(i) == '-'
 			{
 				self.tokens.push_back(Token::Subtract);
 			}
-			else if chars[i] == '*'
+			else if src.at(i) == '*'
 			{
 				self.tokens.push_back(Token::Multiply);
 			}
-			else if chars[i] == '/'
+			else if src.at(i) == '/'
 			{
 				self.tokens.push_back(Token::Divide);
 			}
-			else if chars[i] == '%'
+			else if src.at(i) == '%'
 			{
 				self.tokens.push_back(Token::Modulo);
 			}
-			else if chars[i] == '['
+			else if src.at(i) == '['
 			{
+				open.push(Some(ImplicitArray::default()));
 				self.tokens.push_back(Token::OpenBracket);
 			}
-			else if chars[i] == ']'
+			else if src.at(i) == ']'
 			{
+				open.pop();
 				self.tokens.push_back(Token::CloseBracket);
 			}
-			else if chars[i] == '{'
+			else if src.at(i) == '{'
 			{
+				open.push(None);
 				self.tokens.push_back(Token::OpenBrace);
 			}
-			else if chars[i] == '}'
+			else if src.at(i) == '}'
 			{
+				open.pop();
 				self.tokens.push_back(Token::CloseBrace);
 			}
-			else if chars[i] == '('
+			else if src.at(i) == '('
 			{
+				open.push(None);
 				self.tokens.push_back(Token::OpenParen);
 			}
-			else if chars[i] == ')'
+			else if src.at(i) == ')'
 			{
+				open.pop();
 				self.tokens.push_back(Token::CloseParen);
 			}
-			else if chars[i] == '"'
+			else if src.at(i) == '"'
 			{
-				let end = match s[i + 1..].find('"')
+				let mut end = i + 1;
+
+				while end < slen && src.at(end) != '"'
 				{
-					Some(e) => e + i + 1,
-					None => return Err(box_error("String has no ending quote.")),
-				};
+					end += 1;
+				}
 
-				let val = String::from(&s[i + 1..end]);
+				if end >= slen
+				{
+					return Err(box_error("String has no ending quote."));
+				}
+
+				let val = &s[src.offset(i + 1)..src.offset(end)];
 
-				let laststr = match &self.tokens[self.tokens.len() - 1]
+				// A directive between two strings would stop them being joined.
+				if matches!(self.tokens.back(), Some(Token::Directive(..)))
+					&& matches!(
+						self.tokens
+							.iter()
+							.rev()
+							.find(|t| !matches!(t, Token::Directive(..))),
+						Some(Token::String(_))
+					)
 				{
-					Token::String(s) => Some(s.clone()),
-					_ => None,
-				};
+					let (line, column) = line_column(s, src.offset(i));
 
-				let rlen = self.tokens.len();
+					return Err(box_error(&format!(
+						"Directive before the string at line {line}, column {column} is between \
+						 strings that are joined."
+					)));
+				}
 
-				if let Some(s) = laststr
+				// Check the joined length before copying anything, so overlong strings are never
+				// allocated.
+				if let Some(max) = self.options.max_string_len
 				{
-					self.tokens[rlen - 1] = Token::String(s + &val);
+					let len = match self.tokens.back()
+					{
+						Some(Token::String(last)) => last.len() + val.len(),
+						_ => val.len(),
+					};
+
+					if len > max
+					{
+						let (line, column) = line_column(s, src.offset(i));
+
+						return Err(box_error(&format!(
+							"String at line {line}, column {column} is {len} bytes long, \
+							 exceeding the maximum of {max}."
+						)));
+					}
+				}
+
+				if let Some(Token::String(last)) = self.tokens.back_mut()
+				{
+					last.push_str(val);
 				}
 				else
 				{
-					self.tokens.push_back(Token::String(val));
+					self.tokens.push_back(Token::String(String::from(val)));
 				}
 
 				i = end;
 			}
 			else
 			{
-				return Err(box_error(&format!("Unrecognised token: {}", chars[i])));
+				return Err(box_error(&format!("Unrecognised token: {}", src.at(i))));
 			}
 
 			i += 1;
 		}
 
+		self.push_spans(&source, src.offset(start), src.offset(i));
 		Ok(())
 	}
+	/// Gives the tokens added since the last call the span from `start` to `end` in `source`.
+	fn push_spans(&mut self, source: &Arc<str>, start: usize, end: usize)
+	{
+		while self.spans.len() < self.tokens.len()
+		{
+			self.spans.push_back(Some(Span {
+				source: Arc::clone(source),
+				start,
+				end,
+			}));
+		}
+	}
+	/// If the last token may be followed by a value, meaning a following `-` is the sign of a
+	/// number rather than a subtraction.
+	fn expects_value(&self) -> bool
+	{
+		matches!(
+			self.tokens.back(),
+			None | Some(
+				Token::Equals
+					| Token::Separator
+					| Token::OpenBracket
+					| Token::OpenParen
+					| Token::Add | Token::Subtract
+					| Token::Multiply
+					| Token::Divide | Token::Modulo
+			)
+		)
+	}
+	/// Reads a file and parses its contents to tokens.
 	pub fn parse_file(&mut self, path: &str) -> CfgResult<()>
 	{
 		match fs::read_to_string(path)
@@ -337,12 +770,207 @@ impl Lexer
 			Err(e) => Err(box_error(&format!("Unable to parse file to tokens: {e}.",))),
 		}
 	}
-	pub fn clear(&mut self) { self.tokens.clear(); }
+	/// Clears the lexer, removing all tokens.
+	pub fn clear(&mut self)
+	{
+		self.tokens.clear();
+		self.spans.clear();
+		self.last_span = None;
+	}
+
+	/// Encodes the tokens in the lexer to bytes that can be loaded with [`Lexer::from_bytes`],
+	/// allowing parsed tokens to be cached and reloaded without parsing the original text again.
+	pub fn to_bytes(&self) -> Vec<u8>
+	{
+		let mut bytes = BYTES_HEADER.to_vec();
+
+		let push_str = |bytes: &mut Vec<u8>, s: &str| {
+			bytes.extend_from_slice(&(s.len() as u64).to_le_bytes());
+			bytes.extend_from_slice(s.as_bytes());
+		};
+
+		for token in &self.tokens
+		{
+			match token
+			{
+				Token::Identifier(s) =>
+				{
+					bytes.push(TAG_IDENTIFIER);
+					push_str(&mut bytes, s);
+				}
+				Token::String(s) =>
+				{
+					bytes.push(TAG_STRING);
+					push_str(&mut bytes, s);
+				}
+				Token::Directive(n, v) =>
+				{
+					bytes.push(TAG_DIRECTIVE);
+					push_str(&mut bytes, n);
+					push_str(&mut bytes, v);
+				}
+				Token::Integer(i) =>
+				{
+					bytes.push(TAG_INTEGER);
+					bytes.extend_from_slice(&i.to_le_bytes());
+				}
+				Token::Unsigned(u) =>
+				{
+					bytes.push(TAG_UNSIGNED);
+					bytes.extend_from_slice(&u.to_le_bytes());
+				}
+				Token::Float(f) =>
+				{
+					bytes.push(TAG_FLOAT);
+					bytes.extend_from_slice(&f.to_le_bytes());
+				}
+				_ =>
+				{
+					let index = SIMPLE_TOKENS.iter().position(|t| t == token).unwrap();
+					bytes.push(TAG_SIMPLE + index as u8);
+				}
+			}
+		}
+
+		bytes
+	}
+	/// Creates a new lexer containing the tokens encoded in bytes created with
+	/// [`Lexer::to_bytes`]. The lexer uses the default [`ParseOptions`] for further parsing.
+	pub fn from_bytes(bytes: &[u8]) -> CfgResult<Self>
+	{
+		let mut reader = match bytes.strip_prefix(BYTES_HEADER)
+		{
+			Some(b) => ByteReader { bytes: b },
+			None =>
+			{
+				return Err(box_error(
+					"Unable to load tokens from bytes: Invalid header.",
+				))
+			}
+		};
+		let mut lexer = Lexer::new();
 
+		while let Some(tag) = reader.take(1)
+		{
+			let token = match tag[0]
+			{
+				TAG_IDENTIFIER => reader.read_string().map(Token::Identifier),
+				TAG_STRING => reader.read_string().map(Token::String),
+				TAG_DIRECTIVE => match (reader.read_string(), reader.read_string())
+				{
+					(Some(n), Some(v)) => Some(Token::Directive(n, v)),
+					_ => None,
+				},
+				TAG_INTEGER => reader.read_u64().map(|u| Token::Integer(u as i64)),
+				TAG_UNSIGNED => reader.read_u64().map(Token::Unsigned),
+				TAG_FLOAT => reader.read_u64().map(|u| Token::Float(f64::from_bits(u))),
+				t if t >= TAG_SIMPLE && ((t - TAG_SIMPLE) as usize) < SIMPLE_TOKENS.len() =>
+				{
+					Some(SIMPLE_TOKENS[(t - TAG_SIMPLE) as usize].clone())
+				}
+				t =>
+				{
+					return Err(box_error(&format!(
+						"Unable to load tokens from bytes: Unknown token tag {t}."
+					)))
+				}
+			};
+
+			match token
+			{
+				Some(t) =>
+				{
+					lexer.tokens.push_back(t);
+					lexer.spans.push_back(None);
+				}
+				None =>
+				{
+					return Err(box_error(
+						"Unable to load tokens from bytes: Data is truncated or invalid.",
+					))
+				}
+			}
+		}
+
+		Ok(lexer)
+	}
+
+	/// If the lexer is empty, containing no tokens.
 	pub fn is_empty(&self) -> bool { self.tokens.is_empty() }
+	/// The amount of [`Token::Directive`]s at the front of the lexer.
+	pub(crate) fn leading_directives(&self) -> usize
+	{
+		self.tokens
+			.iter()
+			.take_while(|t| matches!(t, Token::Directive(..)))
+			.count()
+	}
+	/// The amount of tokens the lexer contains.
 	pub fn len(&self) -> usize { self.tokens.len() }
-	pub fn push_front(&mut self, token: Token) { self.tokens.push_front(token); }
-	pub fn pop_front(&mut self) -> Option<Token> { self.tokens.pop_front() }
+	/// Adds a token to the front of the lexer.
+	pub fn push_front(&mut self, token: Token)
+	{
+		self.tokens.push_front(token);
+		self.spans.push_front(None);
+	}
+	/// Removes and returns the token at the front of the lexer, or [`None`] if it is empty.
+	pub fn pop_front(&mut self) -> Option<Token>
+	{
+		self.last_span = self.spans.pop_front().flatten();
+		self.tokens.pop_front()
+	}
+	/// Returns an excerpt of the source line containing the token last removed from the lexer,
+	/// with carets underneath the token, or [`None`] if its position in the source text is not
+	/// known, such as for tokens loaded with [`Lexer::from_bytes`] or added with
+	/// [`Lexer::push_front`].
+	pub fn excerpt(&self) -> Option<String>
+	{
+		let span = self.last_span.as_ref()?;
+		let source = &span.source;
+		let (line, column) = line_column(source, span.start);
+
+		let line_start = source[..span.start].rfind('\n').map_or(0, |n| n + 1);
+		let line_end = source[span.start..]
+			.find('\n')
+			.map_or(source.len(), |n| span.start + n);
+		let text = source[line_start..line_end].trim_end();
+
+		// Keep tabs so the carets line up however wide they are shown.
+		let padding: String = source[line_start..span.start]
+			.chars()
+			.map(|c| {
+				if c == '\t'
+				{
+					'\t'
+				}
+				else
+				{
+					' '
+				}
+			})
+			.collect();
+		let width = source[span.start..span.end.min(line_end)]
+			.trim_end()
+			.chars()
+			.count()
+			.max(1);
+
+		Some(format!(
+			"line {line}, column {column}:\n{text}\n{padding}{}",
+			"^".repeat(width)
+		))
+	}
+	/// Returns an error containing `msg`, followed by an [`excerpt`](Lexer::excerpt) pointing to
+	/// the token last removed from the lexer if its position is known.
+	pub(crate) fn error_at_last(&self, msg: &str) -> Box<CfgError>
+	{
+		match self.excerpt()
+		{
+			Some(excerpt) => box_error(&format!("{msg}\n{excerpt}")),
+			None => box_error(msg),
+		}
+	}
+	/// Returns a reference to the token at the front of the lexer, or [`None`] if it is empty.
 	pub fn peek(&self) -> Option<&Token>
 	{
 		if self.is_empty()
@@ -354,6 +982,7 @@ impl Lexer
 			Some(&self.tokens[0])
 		}
 	}
+	/// Returns references to up to `count` tokens from the front of the lexer.
 	pub fn peek_to(&self, count: usize) -> Vec<&Token>
 	{
 		let mut vector: Vec<&Token> = Vec::new();
@@ -377,6 +1006,8 @@ impl Lexer
 
 		vector
 	}
+	/// Returns the result of `check` on the token at the front of the lexer, or false if it is
+	/// empty.
 	pub fn check(&self, check: fn(&Token) -> bool) -> bool
 	{
 		if self.is_empty()
@@ -388,7 +1019,8 @@ impl Lexer
 			check(&self.tokens[0])
 		}
 	}
-
+	/// Removes and returns the token at the front of the lexer if it passes `check`, otherwise
+	/// returns an error containing `msg`.
 	pub fn expect(&mut self, check: fn(&Token) -> bool, msg: &str) -> CfgResult<Token>
 	{
 		if self.is_empty()
@@ -21,14 +21,23 @@ mod document;
 mod key;
 mod key_value;
 mod lexer;
+mod merge;
+mod options;
+mod path;
+mod schema;
 mod section;
 mod test;
 mod token;
 mod utility;
 
-pub use document::Document;
+pub use document::{Document, SectionId, Validator};
 pub use key::Key;
-pub use key_value::KeyValue;
+pub use key_value::{KeyValue, ValueType};
+pub use lexer::{FromLexer, Lexer};
+pub use merge::{ArrayMergePolicy, ConflictPolicy, MergeConflict, MergePolicy};
+pub use options::{ImplicitIntegerType, ParseOptions};
+pub use path::{parse_path, PathSegment};
+pub use schema::{Schema, SchemaEntry};
 pub use section::Section;
 pub use token::*;
 pub use utility::*;
@@ -0,0 +1,108 @@
+// merge.rs
+//
+// ParseCfg - A simple cfg file parser.
+// Copyright(C) 2024 Michael Furlong.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of
+// the GNU General Public License as published by the Free Software Foundation, either version 3
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program.
+// If not, see <https://www.gnu.org/licenses/>.
+//
+use crate::KeyValue;
+
+/// Determines how a key is resolved when both documents being merged contain it with different
+/// values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ConflictPolicy
+{
+	/// The existing key is kept.
+	Keep,
+	/// The existing key is replaced with the incoming key, including its metadata and comment, but
+	/// keeps its name.
+	#[default]
+	Replace,
+	/// The merge fails.
+	Error,
+}
+
+/// Determines how the values of a key are combined when both documents being merged contain it
+/// with arrays of the same type.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ArrayMergePolicy
+{
+	/// The existing array is replaced with the incoming array.
+	#[default]
+	Replace,
+	/// The incoming elements are appended to the existing array.
+	Append,
+	/// The incoming elements that are not already in the existing array are appended to it.
+	Union,
+}
+impl ArrayMergePolicy
+{
+	/// Combines `incoming` into `existing` if both are arrays of the same type and returns true,
+	/// otherwise leaves `existing` unchanged and returns false.
+	pub fn merge(&self, existing: &mut KeyValue, incoming: &KeyValue) -> bool
+	{
+		match (existing, incoming)
+		{
+			(KeyValue::StringArray(e), KeyValue::StringArray(i)) => self.merge_vec(e, i),
+			(KeyValue::IntegerArray(e), KeyValue::IntegerArray(i)) => self.merge_vec(e, i),
+			(KeyValue::UnsignedArray(e), KeyValue::UnsignedArray(i)) => self.merge_vec(e, i),
+			(KeyValue::FloatArray(e), KeyValue::FloatArray(i)) => self.merge_vec(e, i),
+			_ => return false,
+		}
+
+		true
+	}
+
+	fn merge_vec<T: Clone + PartialEq>(&self, existing: &mut Vec<T>, incoming: &[T])
+	{
+		match self
+		{
+			ArrayMergePolicy::Replace => *existing = incoming.to_vec(),
+			ArrayMergePolicy::Append => existing.extend_from_slice(incoming),
+			ArrayMergePolicy::Union =>
+			{
+				for value in incoming
+				{
+					if !existing.contains(value)
+					{
+						existing.push(value.clone());
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Determines how keys contained in both documents are resolved when merging one
+/// [`Document`](crate::Document) into another.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MergePolicy
+{
+	/// How keys with different values are resolved, unless both values are arrays of the same type.
+	pub conflicts: ConflictPolicy,
+	/// How keys are resolved when both values are arrays of the same type.
+	pub arrays: ArrayMergePolicy,
+}
+
+/// A key that has different values in both documents being merged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeConflict
+{
+	/// The name of the section containing the key.
+	pub section: String,
+	/// The name of the key.
+	pub key: String,
+	/// The value of the key in the document being merged into.
+	pub existing: KeyValue,
+	/// The value of the key in the document being merged.
+	pub incoming: KeyValue,
+}
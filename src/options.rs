@@ -0,0 +1,45 @@
+// options.rs
+//
+// ParseCfg - A simple cfg file parser.
+// Copyright(C) 2024 Michael Furlong.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of
+// the GNU General Public License as published by the Free Software Foundation, either version 3
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program.
+// If not, see <https://www.gnu.org/licenses/>.
+//
+
+/// The type given to integers written without a type postfix.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ImplicitIntegerType
+{
+	/// Implicit integers are always signed integers.
+	#[default]
+	Signed,
+	/// Implicit integers are unsigned integers unless they are negative, or are in an array that
+	/// contains a negative implicit integer.
+	UnsignedIfNonNegative,
+}
+
+/// Options controlling how text is parsed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseOptions
+{
+	/// The type given to integers written without a type postfix, such as `500`.
+	pub implicit_integer_type: ImplicitIntegerType,
+	/// If comments in the form `# @name: value` are parsed as directives and stored in the
+	/// metadata of the key that follows them, rather than being ignored. Only comments on their own
+	/// line where a key may follow are directives; those within or after values are ignored.
+	/// Parsing fails if a directive is not followed by a key, such as one before a section header,
+	/// or is between strings that are joined.
+	pub parse_directives: bool,
+	/// The maximum length of a string value in bytes, after adjacent strings are joined. Longer
+	/// strings cause parsing to fail. If [`None`], the length is unlimited.
+	pub max_string_len: Option<usize>,
+}
@@ -0,0 +1,159 @@
+// path.rs
+//
+// ParseCfg - A simple cfg file parser.
+// Copyright(C) 2024 Michael Furlong.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of
+// the GNU General Public License as published by the Free Software Foundation, either version 3
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program.
+// If not, see <https://www.gnu.org/licenses/>.
+//
+use crate::error::{box_error, CfgResult};
+
+/// A single segment of a path into a document or value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment
+{
+	/// A section, key or table entry name.
+	Key(String),
+	/// An index into an array or tuple.
+	Index(usize),
+}
+
+/// Parses a path such as `Section.Key[2]` into its segments. Segments are separated by dots and may
+/// be followed by any number of bracketed indices. Segments can be enclosed in double quotes so
+/// they may contain dots, brackets or whitespace, such as `"a.b".c`.
+///
+/// The grammar is strict, so a path has only one spelling:
+/// - An unquoted segment is one or more characters that are not whitespace, quotes, dots or
+///   brackets.
+/// - A quoted segment is one or more characters other than a double quote, enclosed in double
+///   quotes. There is no escape sequence for a double quote.
+/// - An index is one or more ASCII digits enclosed in brackets, with no whitespace or sign.
+/// - A segment must be followed by an index, a dot or the end of the path. Only the first segment
+///   may be left out, so a path can start with an index such as `[1].Name`.
+pub fn parse_path(s: &str) -> CfgResult<Vec<PathSegment>>
+{
+	let chars: Vec<char> = s.chars().collect();
+	let mut segments = Vec::new();
+	let mut i = 0;
+
+	if chars.is_empty()
+	{
+		return Err(box_error("Path is empty."));
+	}
+
+	loop
+	{
+		if i < chars.len() && chars[i] == '"'
+		{
+			let start = i + 1;
+			i = start;
+
+			while i < chars.len() && chars[i] != '"'
+			{
+				i += 1;
+			}
+
+			if i >= chars.len()
+			{
+				return Err(box_error("Path segment has no ending quote."));
+			}
+			if i == start
+			{
+				return Err(box_error(&format!("Path has an empty segment at {start}.")));
+			}
+
+			segments.push(PathSegment::Key(chars[start..i].iter().collect()));
+			i += 1;
+		}
+		else
+		{
+			let start = i;
+
+			while i < chars.len() && chars[i] != '.' && chars[i] != '[' && chars[i] != ']'
+			{
+				i += 1;
+			}
+
+			let name: String = chars[start..i].iter().collect();
+
+			if let Some(c) = name.chars().find(|c| c.is_whitespace() || *c == '"')
+			{
+				return Err(box_error(&format!(
+					"Path segment \"{name}\" contains the invalid character {c:?}."
+				)));
+			}
+
+			if !name.is_empty()
+			{
+				segments.push(PathSegment::Key(name));
+			}
+			// Only a path's first segment may be a bare index.
+			else if i >= chars.len() || chars[i] != '[' || !segments.is_empty()
+			{
+				return Err(box_error(&format!("Path has an empty segment at {i}.")));
+			}
+		}
+
+		while i < chars.len() && chars[i] == '['
+		{
+			let start = i + 1;
+			i = start;
+
+			while i < chars.len() && chars[i] != ']'
+			{
+				i += 1;
+			}
+
+			if i >= chars.len()
+			{
+				return Err(box_error("Path index has no closing bracket."));
+			}
+
+			let index: String = chars[start..i].iter().collect();
+
+			if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit())
+			{
+				return Err(box_error(&format!(
+					"Path index \"{index}\" must only contain digits."
+				)));
+			}
+
+			match index.parse::<usize>()
+			{
+				Ok(n) => segments.push(PathSegment::Index(n)),
+				Err(e) =>
+				{
+					return Err(box_error(&format!(
+						"Failed parsing path index \"{index}\": {e}."
+					)))
+				}
+			}
+
+			i += 1;
+		}
+
+		if i >= chars.len()
+		{
+			break;
+		}
+		if chars[i] != '.'
+		{
+			return Err(box_error(&format!(
+				"Unexpected character in path at {i}: {}",
+				chars[i]
+			)));
+		}
+
+		i += 1;
+	}
+
+	Ok(segments)
+}
@@ -0,0 +1,133 @@
+// schema.rs
+//
+// ParseCfg - A simple cfg file parser.
+// Copyright(C) 2024 Michael Furlong.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of
+// the GNU General Public License as published by the Free Software Foundation, either version 3
+// of the License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program.
+// If not, see <https://www.gnu.org/licenses/>.
+//
+use crate::{KeyValue, ValueType};
+
+/// Describes the expected type of a [`Key`](crate::Key) within a section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaEntry
+{
+	/// The name of the section containing the key.
+	pub section: String,
+	/// The name of the key.
+	pub key: String,
+	/// The expected type of the keys' value.
+	pub value_type: ValueType,
+	/// If the key must exist.
+	pub required: bool,
+	/// The value given to the key by [`Document::fill_defaults`](crate::Document::fill_defaults)
+	/// when it is missing.
+	pub default: Option<KeyValue>,
+}
+impl SchemaEntry
+{
+	/// Creates a new entry for the key with the given section, name and type.
+	pub fn new(section: &str, key: &str, value_type: ValueType, required: bool) -> Self
+	{
+		Self {
+			section: String::from(section),
+			key: String::from(key),
+			value_type,
+			required,
+			default: None,
+		}
+	}
+	/// Returns the entry with the given default value.
+	pub fn with_default(mut self, value: KeyValue) -> Self
+	{
+		self.default = Some(value);
+		self
+	}
+
+	/// Checks `value` against the expected type. Returns [`None`] if it matches, otherwise
+	/// [`Some`] containing a description of the mismatch.
+	///
+	/// An array type also matches an empty array of any type, as `[]` is always parsed as a
+	/// [`KeyValue::StringArray`]. A [`KeyValue::Tuple`] never matches an array type; it is reported
+	/// as either containing a mismatched element or, if its elements all have the expected type,
+	/// as needing to be written as an array.
+	pub fn check(&self, value: &KeyValue) -> Option<String>
+	{
+		let found = value.value_type();
+
+		if found == self.value_type
+		{
+			return None;
+		}
+
+		let mismatch = format!("expected {} but found {found}", self.value_type);
+
+		let element = match self.value_type.element_type()
+		{
+			Some(e) => e,
+			None => return Some(mismatch),
+		};
+
+		match value
+		{
+			KeyValue::StringArray(a) if a.is_empty() => None,
+			KeyValue::IntegerArray(a) if a.is_empty() => None,
+			KeyValue::UnsignedArray(a) if a.is_empty() => None,
+			KeyValue::FloatArray(a) if a.is_empty() => None,
+			KeyValue::Tuple(t)
+				if value.is_homogeneous()
+					&& t.first().is_none_or(|v| v.value_type() == element) =>
+			{
+				Some(format!(
+					"is a Tuple of {element} values that should be written as {}",
+					self.value_type
+				))
+			}
+			KeyValue::Tuple(t) => t.iter().position(|v| v.value_type() != element).map(|i| {
+				format!(
+					"expected {} but element {i} is {}",
+					self.value_type,
+					t[i].value_type()
+				)
+			}),
+			_ => Some(mismatch),
+		}
+	}
+}
+
+/// A collection of [`SchemaEntry`]s describing the expected keys of a
+/// [`Document`](crate::Document).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema
+{
+	m_entries: Vec<SchemaEntry>,
+}
+impl Schema
+{
+	/// Creates a new schema with the given entries.
+	pub fn new(entries: &[SchemaEntry]) -> Self
+	{
+		Self {
+			m_entries: entries.to_vec(),
+		}
+	}
+
+	/// Returns an iterator over the contained entries.
+	pub fn iter(&self) -> std::slice::Iter<'_, SchemaEntry> { self.m_entries.iter() }
+
+	/// If the schema is empty, containing no entries.
+	pub fn is_empty(&self) -> bool { self.m_entries.is_empty() }
+	/// The amount of entries the schema contains.
+	pub fn len(&self) -> usize { self.m_entries.len() }
+
+	/// Adds a new entry to the end of the schema.
+	pub fn push(&mut self, entry: SchemaEntry) { self.m_entries.push(entry); }
+}
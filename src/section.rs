@@ -14,17 +14,17 @@
 // You should have received a copy of the GNU General Public License along with this program.
 // If not, see <https://www.gnu.org/licenses/>.
 //
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 
 use crate::{
-	error::{box_error, CfgResult},
+	error::{box_error, CfgError, CfgResult},
 	lexer::{FromLexer, Lexer},
 	name::{as_valid_name, is_valid_name},
-	Key, Token,
+	Key, KeyValue, Token,
 };
 
 /// A named section containing a collection of [`Key`]s.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Section
 {
 	m_name: String,
@@ -48,13 +48,14 @@ impl FromLexer for Section
 	{
 		let is_section_tokens = |lex: &Lexer| -> bool {
 			let len = lex.len();
+			let skip = lex.leading_directives();
 
-			if len < 3
+			if len < skip + 3
 			{
 				return false;
 			}
 
-			let peeks = lex.peek_to(3usize);
+			let peeks = &lex.peek_to(skip + 3)[skip..];
 
 			match peeks[0]
 			{
@@ -88,6 +89,12 @@ impl FromLexer for Section
 				));
 			}
 
+			// Directives before a header have no key to belong to.
+			if lex.leading_directives() > 0
+			{
+				return Err(unattached_directive(lex));
+			}
+
 			lex.pop_front();
 
 			let id = if let Some(Token::Identifier(i)) = lex.pop_front()
@@ -117,11 +124,15 @@ impl FromLexer for Section
 			{
 				break;
 			}
+			if lexer.leading_directives() == lexer.len()
+			{
+				return Err(unattached_directive(lexer));
+			}
 
 			let k = match Key::from_lexer(lexer)
 			{
 				Ok(k) => k,
-				Err(e) => return Err(box_error(&format!("Failed loading key in section: {e}."))),
+				Err(e) => return Err(box_error(&format!("Failed loading key in section: {e}"))),
 			};
 			if !k.is_valid()
 			{
@@ -150,6 +161,16 @@ impl FromLexer for Section
 		Ok(Self::new(&id, &keys))
 	}
 }
+impl Debug for Section
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+	{
+		f.debug_struct("Section")
+			.field("name", &self.m_name)
+			.field("keys", &self.m_keys)
+			.finish()
+	}
+}
 impl Display for Section
 {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
@@ -194,6 +215,8 @@ impl Section
 	pub fn iter(&self) -> std::slice::Iter<'_, Key> { self.m_keys.iter() }
 	/// Returns a mutable iterator over the contained keys.
 	pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Key> { self.m_keys.iter_mut() }
+	/// Returns a vector containing a reference to each of the contained keys, in order.
+	pub fn keys(&self) -> Vec<&Key> { self.m_keys.iter().collect() }
 
 	/// If the section is empty, containing no keys.
 	pub fn is_empty(&self) -> bool { self.m_keys.is_empty() }
@@ -282,6 +305,76 @@ impl Section
 		self.m_keys.push(key);
 		true
 	}
+	/// Adds a new key to the end of the section, renaming it if the section already contains a key
+	/// with the same name by appending `_2`, `_3`, etc. until the name is unique. Returns [`Some`]
+	/// containing the name of the added key, or [`None`] without adding it if the key is not valid.
+	pub fn push_unique(&mut self, mut key: Key) -> Option<String>
+	{
+		if !key.is_valid()
+		{
+			return None;
+		}
+
+		if self.contains(key.name())
+		{
+			let base = key.name().clone();
+			let mut i = 2usize;
+
+			while self.contains(&format!("{base}_{i}"))
+			{
+				i += 1;
+			}
+
+			key.rename(&format!("{base}_{i}"));
+		}
+
+		let name = key.name().clone();
+		self.m_keys.push(key);
+		Some(name)
+	}
+	/// Sets the value of the key with the given name, adding a new key if the section does not
+	/// contain one. Returns the previous value if the key existed. The name may be modified to be
+	/// valid, see [`as_valid_name`] for more details. Names are case-insensitive, so an existing key
+	/// keeps its original name and only its value is replaced.
+	pub fn set(&mut self, name: &str, value: KeyValue) -> Option<KeyValue>
+	{
+		let name = as_valid_name(name, '_');
+
+		match self.get_mut(&name)
+		{
+			Some(key) => Some(std::mem::replace(&mut key.value, value)),
+			None =>
+			{
+				self.m_keys.push(Key::new(&name, value));
+				None
+			}
+		}
+	}
+	/// Adds a key to the end of the section, or replaces the key with the same name if the section
+	/// already contains one. Returns the replaced key if there was one. Names are case-insensitive,
+	/// so a replaced key keeps the original name of the existing key rather than the name of `key`.
+	/// Returns `key` back without adding it if it is not valid.
+	pub fn push_or_replace(&mut self, mut key: Key) -> Option<Key>
+	{
+		if !key.is_valid()
+		{
+			return Some(key);
+		}
+
+		match self.index_of(key.name())
+		{
+			Some(index) =>
+			{
+				key.rename(&self.m_keys[index].name().clone());
+				Some(std::mem::replace(&mut self.m_keys[index], key))
+			}
+			None =>
+			{
+				self.m_keys.push(key);
+				None
+			}
+		}
+	}
 	/// Inserts a new key at the given index. Returns true on success or false if the key is not
 	/// valid or the section already contains a key with the same name.
 	pub fn insert(&mut self, index: usize, key: Key) -> bool
@@ -318,4 +411,51 @@ impl Section
 	}
 	/// Clears the section, removing all keys.
 	pub fn clear(&mut self) { self.m_keys.clear(); }
+	/// Clears the section, removing all keys while keeping the memory allocated for them, and
+	/// ensures there is room for at least `capacity` keys without reallocating.
+	pub fn clear_and_reserve(&mut self, capacity: usize)
+	{
+		self.m_keys.clear();
+		self.m_keys.reserve(capacity);
+	}
+	/// The amount of keys the section can contain without reallocating.
+	pub fn capacity(&self) -> usize { self.m_keys.capacity() }
+
+	/// Returns a new section with the given name containing clones of the keys that match `pred`.
+	/// The name may be modified, see [`as_valid_name`] for more details.
+	pub fn extract(&self, pred: impl Fn(&Key) -> bool, new_name: &str) -> Section
+	{
+		Self {
+			m_name: as_valid_name(new_name, '_'),
+			m_keys: self.m_keys.iter().filter(|k| pred(k)).cloned().collect(),
+		}
+	}
+	/// Removes the keys that match `pred` from the section and returns them in a new section with
+	/// the given name. The name may be modified, see [`as_valid_name`] for more details.
+	pub fn split_off(&mut self, pred: impl Fn(&Key) -> bool, new_name: &str) -> Section
+	{
+		let (keys, kept): (Vec<Key>, Vec<Key>) = std::mem::take(&mut self.m_keys)
+			.into_iter()
+			.partition(|k| pred(k));
+
+		self.m_keys = kept;
+
+		Self {
+			m_name: as_valid_name(new_name, '_'),
+			m_keys: keys,
+		}
+	}
+}
+
+/// Removes the directive at the front of the lexer and returns an error stating it is not followed
+/// by a key.
+fn unattached_directive(lexer: &mut Lexer) -> Box<CfgError>
+{
+	match lexer.pop_front()
+	{
+		Some(Token::Directive(name, _)) => lexer.error_at_last(&format!(
+			"Failed loading section: The directive @{name} is not followed by a key."
+		)),
+		_ => box_error("Failed loading section: Expected a directive."),
+	}
 }
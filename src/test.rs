@@ -17,7 +17,13 @@
 #[cfg(test)]
 mod tests
 {
-	use crate::{lexer::*, Document, Key, KeyValue, Section};
+	use std::str::FromStr;
+
+	use crate::{
+		error::box_error, lexer::*, parse_path, ArrayMergePolicy, ConflictPolicy, Document,
+		ImplicitIntegerType, Key, KeyValue, MergeConflict, MergePolicy, ParseOptions, PathSegment,
+		Schema, SchemaEntry, Section, Token, Validator, ValueType,
+	};
 
 	const TEST_STRING: &str = "\tOrange= \"Banana\" # Comment";
 	const TEST_STRING_APPEND: &str = "\tOrange= \"Ban\" \"ana\" # Comment";
@@ -37,7 +43,82 @@ mod tests
 		"[\tTest ]\nFruit = \"Oranges\"# Comment\nElephants = \"No Thanks!\"";
 	const TEST_DOCUMENT: &str =
 		"[Size]# Comment\nWidth = 800u#Bon\nHeight = 600u#Lem\n[Position]\nX = 20\nY = 40";
+	const TEST_DOCUMENT_UTF8: &str =
+		"[Greeting] # Grüße\nText = \"Grüße, 世界\"\nCount = 3 # naïve comment";
+
+	#[test]
+	fn lexer_test()
+	{
+		let mut ascii = Lexer::new();
+		let mut utf8 = Lexer::new();
+
+		match ascii.parse_ascii(TEST_DOCUMENT)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+		match utf8.parse_utf8(TEST_DOCUMENT)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(ascii.len(), utf8.len());
+
+		while let Some(token) = ascii.pop_front()
+		{
+			assert_eq!(Some(token), utf8.pop_front());
+		}
+
+		assert!(ascii.parse_ascii(TEST_DOCUMENT_UTF8).is_err());
+
+		let mut lexer = Lexer::new();
+
+		match lexer.parse_string(TEST_DOCUMENT_UTF8)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(
+			lexer.peek_to(3)[1],
+			&Token::Identifier(String::from("Greeting"))
+		);
+
+		let doc = match Document::from_str(TEST_DOCUMENT_UTF8)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
 
+		assert_eq!(
+			doc.get("Greeting").unwrap().get("Text").unwrap().value,
+			KeyValue::String(String::from("Grüße, 世界"))
+		);
+		assert_eq!(
+			doc.get("Greeting").unwrap().get("Count").unwrap().value,
+			KeyValue::Integer(3i64)
+		);
+	}
 	#[test]
 	fn key_test()
 	{
@@ -537,4 +618,1665 @@ mod tests
 			KeyValue::Integer(40i64)
 		);
 	}
+	#[test]
+	fn section_extract_test()
+	{
+		let mut sect = Section::new(
+			"Player",
+			&[
+				Key::new("Name", KeyValue::String(String::from("Gary"))),
+				Key::new("Speed", KeyValue::Float(2.5f64)),
+				Key::new("Lives", KeyValue::Integer(3i64)),
+				Key::new("Scale", KeyValue::Float(0.75f64)),
+			],
+		);
+		let is_float = |k: &Key| matches!(k.value, KeyValue::Float(_));
+
+		let floats = sect.extract(is_float, "Floats");
+
+		assert_eq!(*floats.name(), "Floats");
+		assert_eq!(floats.len(), 2);
+		assert_eq!(floats.get_at(0).unwrap().value, KeyValue::Float(2.5f64));
+		assert_eq!(floats.get_at(1).unwrap().value, KeyValue::Float(0.75f64));
+		assert_eq!(sect.len(), 4);
+
+		let moved = sect.split_off(is_float, "Floats");
+
+		assert_eq!(moved, floats);
+		assert_eq!(sect.len(), 2);
+		assert!(sect.contains("Name"));
+		assert!(sect.contains("Lives"));
+		assert!(!sect.contains("Speed"));
+		assert!(!sect.contains("Scale"));
+	}
+	#[test]
+	fn document_validate_test()
+	{
+		let doc = match Document::from_str(TEST_DOCUMENT)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let width_range: Validator = Box::new(|d| {
+			let width = d.get("Size").and_then(|s| s.get("Width")).map(|k| &k.value);
+
+			match width
+			{
+				Some(KeyValue::Unsigned(w)) if (640..=3840).contains(w) => Ok(()),
+				_ => Err(box_error("Width must be between 640 and 3840.")),
+			}
+		});
+		let x_range: Validator = Box::new(|d| {
+			let x = d.get("Position").and_then(|s| s.get("X")).map(|k| &k.value);
+
+			match x
+			{
+				Some(KeyValue::Integer(x)) if *x >= 100 => Ok(()),
+				_ => Err(box_error("X must be at least 100.")),
+			}
+		});
+		let has_colour: Validator = Box::new(|d| {
+			if d.contains("Colour")
+			{
+				Ok(())
+			}
+			else
+			{
+				Err(box_error("Missing Colour section."))
+			}
+		});
+
+		assert!(doc.validate_with(&[]).is_ok());
+		assert!(doc.validate_with(&[width_range]).is_ok());
+
+		let err = match doc.validate_with(&[x_range, has_colour])
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(err.contains("X must be at least 100."));
+		assert!(err.contains("Missing Colour section."));
+	}
+	#[test]
+	fn parse_options_test()
+	{
+		let unsigned = ParseOptions {
+			implicit_integer_type: ImplicitIntegerType::UnsignedIfNonNegative,
+			..Default::default()
+		};
+
+		for (options, positive) in [
+			(ParseOptions::default(), KeyValue::Integer(500i64)),
+			(unsigned, KeyValue::Unsigned(500u64)),
+		]
+		{
+			let mut lexer = Lexer::with_options(options);
+
+			match lexer.parse_string(TEST_IMP_INT)
+			{
+				Ok(_) =>
+				{}
+				Err(e) =>
+				{
+					println!("{e}");
+					panic!()
+				}
+			};
+
+			let key = match Key::from_lexer(&mut lexer)
+			{
+				Ok(k) => k,
+				Err(e) =>
+				{
+					println!("{e}");
+					panic!()
+				}
+			};
+
+			assert_eq!(key.value, positive);
+
+			match lexer.parse_string("Health = -5")
+			{
+				Ok(_) =>
+				{}
+				Err(e) =>
+				{
+					println!("{e}");
+					panic!()
+				}
+			};
+
+			let key = match Key::from_lexer(&mut lexer)
+			{
+				Ok(k) => k,
+				Err(e) =>
+				{
+					println!("{e}");
+					panic!()
+				}
+			};
+
+			assert_eq!(key.value, KeyValue::Integer(-5i64));
+		}
+
+		let mut lexer = Lexer::new();
+
+		match lexer.parse_string("Values = (-5, -.5, -2.5i)")
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let key = match Key::from_lexer(&mut lexer)
+		{
+			Ok(k) => k,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(
+			key.value,
+			KeyValue::Tuple(vec![
+				KeyValue::Integer(-5i64),
+				KeyValue::Float(-0.5f64),
+				KeyValue::Integer(-2i64)
+			])
+		);
+		assert!(Lexer::new().parse_string("Health = -5u").is_err());
+
+		let unsigned = ParseOptions {
+			implicit_integer_type: ImplicitIntegerType::UnsignedIfNonNegative,
+			..Default::default()
+		};
+		let values = |text: &str| {
+			Document::from_str_with(text, &unsigned)
+				.unwrap()
+				.get("S")
+				.unwrap()
+				.get("A")
+				.unwrap()
+				.value
+				.clone()
+		};
+
+		assert_eq!(
+			values("[S] A = [1, -2]"),
+			KeyValue::IntegerArray(vec![1, -2])
+		);
+		assert_eq!(
+			values("[S] A = [-2, 1]"),
+			KeyValue::IntegerArray(vec![-2, 1])
+		);
+		assert_eq!(
+			values("[S] A = [1, 2]"),
+			KeyValue::UnsignedArray(vec![1, 2])
+		);
+		assert_eq!(
+			values("[S] A = ([1], -2, [3, -5])"),
+			KeyValue::Tuple(vec![
+				KeyValue::UnsignedArray(vec![1]),
+				KeyValue::Integer(-2),
+				KeyValue::IntegerArray(vec![3, -5]),
+			])
+		);
+		assert!(Document::from_str_with("[S] A = [1u, -2]", &unsigned).is_err());
+	}
+	#[test]
+	fn debug_test()
+	{
+		let doc = Document::new(&[Section::new(
+			"Window",
+			&[
+				Key::new("Width", KeyValue::Unsigned(800u64)),
+				Key::new(
+					"Title",
+					KeyValue::Table(vec![Key::new(
+						"Text",
+						KeyValue::String(String::from("Hello")),
+					)]),
+				),
+			],
+		)]);
+
+		let debug = format!("{doc:?}");
+
+		assert!(!debug.contains("m_"));
+		assert_eq!(
+			debug,
+			"Document { sections: [Section { name: \"Window\", keys: [Key { name: \"Width\", \
+			 value: Unsigned(800) }, Key { name: \"Title\", value: Table([Key { name: \"Text\", \
+			 value: String(\"Hello\") }]) }] }] }"
+		);
+		let pretty = format!("{doc:#?}");
+
+		assert!(pretty.starts_with("Document {\n    sections: [\n        Section {\n"));
+		assert!(pretty.contains("name: \"Text\",\n"));
+	}
+	#[test]
+	fn directive_test()
+	{
+		const TEST_DIRECTIVES: &str = "[Stats]\n# @type: integer\n# Not a directive\nHealth = \
+		                               500\n# @type: string\nName = \"Gary\"";
+
+		let options = ParseOptions {
+			parse_directives: true,
+			..Default::default()
+		};
+		let doc = match Document::from_str_with(TEST_DIRECTIVES, &options)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let stats = doc.get("Stats").unwrap();
+		let health = stats.get("Health").unwrap();
+
+		assert_eq!(health.value, KeyValue::Integer(500i64));
+		assert_eq!(health.metadata().len(), 1);
+		assert_eq!(health.metadata().get("type").unwrap(), "integer");
+		assert_eq!(
+			stats.get("Name").unwrap().metadata().get("type").unwrap(),
+			"string"
+		);
+		assert_eq!(health.to_string(), "# @type: integer\nHealth = 500");
+
+		for text in [
+			"[S]\nA = [1, # @x: y\n 2]\nB = 3",
+			"[S]\nA = (1, # @x: y\n \"2\")\nB = 3",
+			"[S]\nA = { a = 1, # @x: y\n }\nB = 3",
+			"[S]\nA = # @x: y\n 1\nB = 3",
+			"[S]\nA = 1 # @x: y\nB = 3",
+		]
+		{
+			let doc = match Document::from_str_with(text, &options)
+			{
+				Ok(d) => d,
+				Err(e) =>
+				{
+					println!("{text}: {e}");
+					panic!()
+				}
+			};
+
+			assert_eq!(doc, Document::from_str(text).unwrap());
+			assert!(doc
+				.get("S")
+				.unwrap()
+				.iter()
+				.all(|k| k.metadata().is_empty()));
+		}
+
+		// Directives must be followed by a key.
+		for (text, expected) in [
+			(
+				"# @section: stats\n[Stats]\nA = 1",
+				"@section is not followed by a key",
+			),
+			(
+				"[Stats]\nA = 1\n# @unused: true",
+				"@unused is not followed by a key",
+			),
+			(
+				"[A]\nB = 1\n# @x: y\n[C]\nD = 2",
+				"@x is not followed by a key",
+			),
+			(
+				"[S]\nA = \"a\"\n# @x: y\n\"b\"",
+				"between strings that are joined",
+			),
+		]
+		{
+			let err = Document::from_str_with(text, &options)
+				.unwrap_err()
+				.to_string();
+
+			assert!(err.contains(expected), "{err}");
+			assert!(Document::from_str(text).is_ok());
+		}
+
+		let mut key = Key::new("A", KeyValue::Integer(1));
+
+		assert!(!key.set_metadata("x", "a\nB = 2"));
+		assert!(!key.set_metadata("not valid", "a"));
+		assert!(key.set_metadata("unit", " px "));
+		assert!(key.metadata().get("x").is_none());
+
+		let doc = Document::from_str_with(&Section::new("S", &[key.clone()]).to_string(), &options)
+			.unwrap();
+		let section = doc.get("S").unwrap();
+
+		assert_eq!(section.len(), 1);
+		assert_eq!(section.get("A").unwrap(), &key);
+		assert_eq!(key.remove_metadata("unit"), Some(String::from("px")));
+		assert!(key.metadata().is_empty());
+
+		let doc = match Document::from_str(TEST_DIRECTIVES)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert!(doc
+			.get("Stats")
+			.unwrap()
+			.iter()
+			.all(|k| k.metadata().is_empty()));
+	}
+	#[test]
+	fn cross_section_duplicates_test()
+	{
+		let doc = match Document::from_str(
+			"[Graphics]\nDebug = 1\nWidth = 800u\n[Audio]\nVolume = 0.5\n[Network]\ndebug = \
+			 0\nPort = 8080u",
+		)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(
+			doc.cross_section_duplicates(),
+			vec![(
+				String::from("Debug"),
+				vec![String::from("Graphics"), String::from("Network")]
+			)]
+		);
+		assert!(Document::new(&[]).cross_section_duplicates().is_empty());
+	}
+	#[test]
+	fn string_concat_test()
+	{
+		// Large enough that copying the joined string for each fragment, hundreds of gigabytes in
+		// total, would stall the test, while appending in place copies a few megabytes.
+		const FRAGMENTS: usize = 200_000;
+		const FRAGMENT: &str = "0123456789abcdef";
+
+		let mut lexer = Lexer::new();
+		let text = String::from("Text = ") + &format!("\"{FRAGMENT}\" ").repeat(FRAGMENTS);
+
+		match lexer.parse_string(&text)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(lexer.len(), 3);
+		assert_eq!(
+			lexer.peek_to(3)[2],
+			&Token::String(FRAGMENT.repeat(FRAGMENTS))
+		);
+
+		lexer.clear();
+
+		match lexer.parse_string("\"Leading\" \"string\"")
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(
+			lexer.pop_front(),
+			Some(Token::String(String::from("Leadingstring")))
+		);
+		assert!(lexer.is_empty());
+	}
+	#[test]
+	fn table_test()
+	{
+		let mut lexer = Lexer::new();
+
+		match lexer.parse_string(TEST_TABLE)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let mut key = match Key::from_lexer(&mut lexer)
+		{
+			Ok(k) => k,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert!(key.value.table_contains("name"));
+		assert!(!key.value.table_contains("Version"));
+		assert_eq!(
+			key.value.table_get("NAME").unwrap(),
+			&Key::new("Name", KeyValue::String(String::from("C++")))
+		);
+
+		key.value.table_get_mut("Name").unwrap().value = KeyValue::String(String::from("Rust"));
+
+		assert_eq!(
+			key.value.table_get("Name").unwrap().value,
+			KeyValue::String(String::from("Rust"))
+		);
+
+		assert!(key
+			.value
+			.table_insert(Key::new("Version", KeyValue::Integer(2021i64))));
+		assert!(!key
+			.value
+			.table_insert(Key::new("version", KeyValue::Integer(2018i64))));
+		assert_eq!(
+			key.value.table_get("Version").unwrap().value,
+			KeyValue::Integer(2021i64)
+		);
+
+		let mut string = KeyValue::String(String::from("Not a table"));
+
+		assert!(string.table_get("Name").is_none());
+		assert!(!string.table_insert(Key::new("Name", KeyValue::default())));
+	}
+	#[test]
+	fn schema_test()
+	{
+		let schema = Schema::new(&[
+			SchemaEntry::new("Scores", "Values", ValueType::IntegerArray, true),
+			SchemaEntry::new("Scores", "Name", ValueType::String, false),
+		]);
+
+		let mut doc = match Document::from_str("[Scores]\nValues = [1, 2, 3]")
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert!(doc.validate(&schema).is_ok());
+
+		let values = doc.get_mut("Scores").unwrap().get_mut("Values").unwrap();
+		values.value = KeyValue::Tuple(vec![
+			KeyValue::Integer(1i64),
+			KeyValue::String(String::from("2")),
+		]);
+
+		assert!(!values.value.is_homogeneous());
+
+		let err = match doc.validate(&schema)
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(err.contains("Key Scores.Values expected IntegerArray but element 1 is String."));
+
+		let values = doc.get_mut("Scores").unwrap().get_mut("Values").unwrap();
+		values.value = KeyValue::Tuple(vec![KeyValue::Integer(1i64), KeyValue::Integer(2i64)]);
+
+		assert!(values.value.is_homogeneous());
+		assert!(doc.validate(&schema).is_err());
+
+		doc.get_mut("Scores")
+			.unwrap()
+			.get_mut("Values")
+			.unwrap()
+			.value = KeyValue::String(String::from("1, 2"));
+
+		let err = match doc.validate(&schema)
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(err.contains("expected IntegerArray but found String."));
+
+		doc.get_mut("Scores").unwrap().remove("Values");
+
+		assert!(doc.validate(&schema).is_err());
+
+		let mut doc = Document::from_str("[Points]\nMixed = (1, \"two\")\nEmpty = ()").unwrap();
+		let empty = Schema::default();
+
+		assert!(doc.validate(&empty).is_ok());
+
+		doc.get_mut("Points").unwrap().push(Key::new(
+			"Floats",
+			KeyValue::Tuple(vec![KeyValue::Float(1.0), KeyValue::Float(2.5)]),
+		));
+
+		let err = match doc.validate(&empty)
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(err.contains("Key Points.Floats is a Tuple that should be written as FloatArray."));
+		assert!(!err.contains("Mixed"));
+	}
+	#[test]
+	fn section_push_unique_test()
+	{
+		let mut sect = Section::new("Points", &[]);
+
+		assert_eq!(
+			sect.push_unique(Key::new("X", KeyValue::Integer(1i64))),
+			Some(String::from("X"))
+		);
+		assert_eq!(
+			sect.push_unique(Key::new("X", KeyValue::Integer(2i64))),
+			Some(String::from("X_2"))
+		);
+		assert_eq!(
+			sect.push_unique(Key::new("x", KeyValue::Integer(3i64))),
+			Some(String::from("x_3"))
+		);
+
+		assert_eq!(sect.len(), 3);
+		assert_eq!(sect.get("X").unwrap().value, KeyValue::Integer(1i64));
+		assert_eq!(sect.get("X_2").unwrap().value, KeyValue::Integer(2i64));
+		assert_eq!(sect.get("X_3").unwrap().value, KeyValue::Integer(3i64));
+
+		assert_eq!(
+			sect.push_unique(Key::with_unchecked_name("X Y", KeyValue::Integer(4i64))),
+			None
+		);
+		assert_eq!(sect.len(), 3);
+	}
+	#[test]
+	fn lexer_clone_test()
+	{
+		let mut lexer = Lexer::new();
+
+		match lexer.parse_string(TEST_SECTION)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let len = lexer.len();
+		let mut snapshot = lexer.clone();
+
+		// Loading a key fails on the section header but consumes tokens from the snapshot.
+		assert!(Key::from_lexer(&mut snapshot).is_err());
+		assert!(snapshot.len() < len);
+		assert_eq!(lexer.len(), len);
+
+		let sect = match Section::from_lexer(&mut lexer)
+		{
+			Ok(s) => s,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(*sect.name(), "Test");
+		assert_eq!(sect.len(), 2);
+	}
+	#[test]
+	fn merge_test()
+	{
+		let parse = |s: &str| match Document::from_str(s)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+		let tags = |d: &Document| d.get("Post").unwrap().get("Tags").unwrap().value.clone();
+		let strings = |a: &[&str]| KeyValue::StringArray(a.iter().map(|s| s.to_string()).collect());
+
+		let base = parse("[Post]\nTags = [\"a\", \"b\"]\nTitle = \"Old\"");
+		let other = parse("[Post]\ntags = [\"b\", \"c\"]\nTitle = \"New\"\n[Meta]\nViews = 3");
+
+		for (arrays, expected) in [
+			(ArrayMergePolicy::Replace, strings(&["b", "c"])),
+			(ArrayMergePolicy::Append, strings(&["a", "b", "b", "c"])),
+			(ArrayMergePolicy::Union, strings(&["a", "b", "c"])),
+		]
+		{
+			let mut doc = base.clone();
+			let policy = MergePolicy {
+				arrays,
+				..Default::default()
+			};
+
+			match doc.merge(&other, &policy)
+			{
+				Ok(_) =>
+				{}
+				Err(e) =>
+				{
+					println!("{e}");
+					panic!()
+				}
+			};
+
+			assert_eq!(tags(&doc), expected);
+			assert_eq!(doc.get("Post").unwrap().get_at(0).unwrap().name(), "Tags");
+			assert_eq!(
+				doc.get("Post").unwrap().get("Title").unwrap().value,
+				KeyValue::String(String::from("New"))
+			);
+			assert_eq!(
+				doc.get("Meta").unwrap().get("Views").unwrap().value,
+				KeyValue::Integer(3i64)
+			);
+		}
+
+		let mut doc = base.clone();
+		let policy = MergePolicy {
+			conflicts: ConflictPolicy::Keep,
+			arrays: ArrayMergePolicy::Union,
+		};
+
+		assert!(doc.merge(&other, &policy).is_ok());
+		assert_eq!(
+			doc.get("Post").unwrap().get("Title").unwrap().value,
+			KeyValue::String(String::from("Old"))
+		);
+		assert_eq!(tags(&doc), strings(&["a", "b", "c"]));
+
+		let mut doc = base.clone();
+		let policy = MergePolicy {
+			conflicts: ConflictPolicy::Error,
+			..Default::default()
+		};
+
+		let err = doc.merge(&other, &policy).unwrap_err().to_string();
+
+		assert!(err.ends_with("has conflicting values."), "{err}");
+		assert_eq!(tags(&doc), strings(&["a", "b"]));
+		assert!(!doc.contains("Meta"));
+
+		let mut doc = Document::new(&[Section::new(
+			"Post",
+			&[Key::new("Title", KeyValue::Integer(1)).with_comment("Old comment.")],
+		)]);
+		let other = Document::new(&[Section::new(
+			"Post",
+			&[Key::new("title", KeyValue::Integer(2)).with_comment("New comment.")],
+		)]);
+
+		doc.merge(&other, &MergePolicy::default()).unwrap();
+
+		let title = doc.get("Post").unwrap().get_at(0).unwrap();
+
+		assert_eq!(title.name(), "Title");
+		assert_eq!(title.value, KeyValue::Integer(2));
+		assert_eq!(title.comment(), Some("New comment."));
+	}
+	#[test]
+	fn lexer_bytes_test()
+	{
+		let mut lexer = Lexer::new();
+
+		match lexer.parse_string(TEST_DOCUMENT)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+		match lexer
+			.parse_string("[Extra]\nName = \"Grüße\"\nOffset = -2.5\nTable = { a = (1, [2u]) }")
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let bytes = lexer.to_bytes();
+
+		let mut loaded = match Lexer::from_bytes(&bytes)
+		{
+			Ok(l) => l,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(loaded.len(), lexer.len());
+
+		let expected = match Document::from_lexer(&mut lexer)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+		let doc = match Document::from_lexer(&mut loaded)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(doc.len(), 3);
+		assert!(doc.iter().eq(expected.iter()));
+
+		// Cut off within the length of the first identifier.
+		assert!(Lexer::from_bytes(&bytes[..12]).is_err());
+		assert!(Lexer::from_bytes(b"Not tokens").is_err());
+		assert!(Lexer::from_bytes(&Lexer::new().to_bytes())
+			.unwrap()
+			.is_empty());
+	}
+	#[test]
+	fn string_array_access_test()
+	{
+		let mut lexer = Lexer::new();
+
+		match lexer.parse_string(TEST_ARRAY_STR)
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let key = match Key::from_lexer(&mut lexer)
+		{
+			Ok(k) => k,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(key.value.as_str_slice().unwrap().len(), 3);
+		assert_eq!(
+			key.value.as_str_iter().unwrap().collect::<Vec<&str>>(),
+			vec!["One", "Two", "Three"]
+		);
+		assert!(KeyValue::IntegerArray(vec![1i64]).as_str_slice().is_none());
+		assert!(KeyValue::String(String::from("One"))
+			.as_str_iter()
+			.is_none());
+	}
+	#[test]
+	fn section_id_test()
+	{
+		let mut doc = match Document::from_str(TEST_DOCUMENT)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let position = doc.id_of("position").unwrap();
+
+		assert_eq!(*doc.get_by_id(position).unwrap().name(), "Position");
+		assert!(doc.insert(0, Section::new("Colour", &[])));
+		assert!(doc.push(Section::new("Audio", &[])));
+		assert_eq!(*doc.get_by_id(position).unwrap().name(), "Position");
+
+		doc.get_by_id_mut(position).unwrap().rename("Location");
+
+		assert_eq!(doc.index_of_id(position), Some(2));
+		assert_eq!(doc.id_of("Location"), Some(position));
+
+		assert!(doc.remove("Size"));
+		assert_eq!(*doc.get_by_id(position).unwrap().name(), "Location");
+		assert!(doc.remove("Location"));
+		assert!(doc.get_by_id(position).is_none());
+
+		assert!(doc.push(Section::new("Location", &[])));
+		assert_ne!(doc.id_of("Location"), Some(position));
+	}
+	#[test]
+	fn annotated_array_test()
+	{
+		let parse = |s: &str| {
+			let mut lexer = Lexer::new();
+
+			match lexer.parse_string(s)
+			{
+				Ok(_) =>
+				{}
+				Err(e) =>
+				{
+					println!("{e}");
+					panic!()
+				}
+			};
+
+			Key::from_lexer(&mut lexer).map(|k| k.value)
+		};
+
+		assert_eq!(
+			parse("Scores = f[1, 2, 3]").unwrap(),
+			KeyValue::FloatArray(vec![1f64, 2f64, 3f64])
+		);
+		assert_eq!(
+			parse("Scores = u[1, 2]").unwrap(),
+			KeyValue::UnsignedArray(vec![1u64, 2u64])
+		);
+		assert_eq!(
+			parse("Scores = I[2.5, 3u, -4]").unwrap(),
+			KeyValue::IntegerArray(vec![2i64, 3i64, -4i64])
+		);
+		assert_eq!(parse("Scores = f[]").unwrap(), KeyValue::FloatArray(vec![]));
+		assert!(parse("Scores = u[1, -2]").is_err());
+		assert!(parse("Scores = f[1, \"2\"]").is_err());
+		assert!(parse("Scores = x[1, 2]").is_err());
+		assert!(parse("Scores = f[1, 2").is_err());
+
+		for value in [
+			KeyValue::FloatArray(vec![1f64, 2.5f64]),
+			KeyValue::UnsignedArray(vec![7u64]),
+			KeyValue::IntegerArray(vec![]),
+		]
+		{
+			let key = Key::new("Scores", value.clone());
+
+			assert_eq!(parse(&key.to_string()).unwrap(), value);
+		}
+	}
+	#[test]
+	fn find_map_value_test()
+	{
+		let mut doc = match Document::from_str(
+			"[Player]\nName = \"Gary\"\nStats = (\"Level\", 4, { Health = 100 })\n[Enemy]\nLevel \
+			 = 9",
+		)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let mut visited = 0;
+		let found = doc.find_map_value_mut(|v| {
+			visited += 1;
+
+			match v
+			{
+				KeyValue::Integer(i) =>
+				{
+					*i *= 10;
+					Some(*i)
+				}
+				_ => None,
+			}
+		});
+
+		assert_eq!(found, Some(40i64));
+		assert_eq!(visited, 4);
+		assert_eq!(
+			doc.get("Player").unwrap().get("Stats").unwrap().value,
+			KeyValue::Tuple(vec![
+				KeyValue::String(String::from("Level")),
+				KeyValue::Integer(40i64),
+				KeyValue::Table(vec![Key::new("Health", KeyValue::Integer(100i64))])
+			])
+		);
+		assert_eq!(
+			doc.get("Enemy").unwrap().get("Level").unwrap().value,
+			KeyValue::Integer(9i64)
+		);
+		assert_eq!(doc.find_map_value_mut(|_| None::<()>), None);
+	}
+	#[test]
+	fn section_reuse_test()
+	{
+		let mut sect = Section::new("Frame", &[]);
+
+		sect.clear_and_reserve(8);
+
+		assert!(sect.is_empty());
+		assert!(sect.capacity() >= 8);
+
+		for frame in 0..3i64
+		{
+			let capacity = sect.capacity();
+
+			sect.clear_and_reserve(4);
+
+			assert!(sect.is_empty());
+			assert_eq!(sect.capacity(), capacity);
+
+			for i in 0..6i64
+			{
+				assert!(sect.push(Key::new(&format!("Key{i}"), KeyValue::Integer(frame * i))));
+			}
+
+			assert_eq!(sect.len(), 6);
+			assert_eq!(
+				sect.get("Key5").unwrap().value,
+				KeyValue::Integer(frame * 5)
+			);
+		}
+	}
+	#[test]
+	fn json_test()
+	{
+		let value = KeyValue::Table(vec![
+			Key::new("Name", KeyValue::String(String::from("Say \"hi\"\n"))),
+			Key::new("Scores", KeyValue::FloatArray(vec![1.5f64, f64::NAN])),
+			Key::new("Empty", KeyValue::IntegerArray(vec![])),
+			Key::new(
+				"Pair",
+				KeyValue::Tuple(vec![
+					KeyValue::Unsigned(3u64),
+					KeyValue::Table(vec![Key::new("X", KeyValue::Integer(-1i64))]),
+				]),
+			),
+		]);
+
+		assert_eq!(
+			value.to_json_string(false),
+			"{\"Name\":\"Say \
+			 \\\"hi\\\"\\n\",\"Scores\":[1.5,null],\"Empty\":[],\"Pair\":[3,{\"X\":-1}]}"
+		);
+		assert_eq!(
+			value.to_json_string(true),
+			"{\n\t\"Name\": \"Say \\\"hi\\\"\\n\",\n\t\"Scores\": \
+			 [\n\t\t1.5,\n\t\tnull\n\t],\n\t\"Empty\": [],\n\t\"Pair\": \
+			 [\n\t\t3,\n\t\t{\n\t\t\t\"X\": -1\n\t\t}\n\t]\n}"
+		);
+		assert_eq!(KeyValue::Float(2f64).to_json_string(true), "2");
+	}
+	#[test]
+	fn schema_defaults_test()
+	{
+		let schema = Schema::new(&[
+			SchemaEntry::new("Size", "Width", ValueType::Unsigned, true)
+				.with_default(KeyValue::Unsigned(1280u64)),
+			SchemaEntry::new("Size", "Fullscreen", ValueType::Integer, false)
+				.with_default(KeyValue::Integer(0i64)),
+			SchemaEntry::new("Audio", "Volume", ValueType::Float, false)
+				.with_default(KeyValue::Float(0.5f64)),
+			SchemaEntry::new("Audio", "Device", ValueType::String, false),
+		]);
+
+		let mut doc = match Document::from_str(TEST_DOCUMENT)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert!(doc.fill_defaults(&schema).is_ok());
+
+		let size = doc.get("Size").unwrap();
+
+		assert_eq!(size.get("Width").unwrap().value, KeyValue::Unsigned(800u64));
+		assert_eq!(
+			size.get("Fullscreen").unwrap().value,
+			KeyValue::Integer(0i64)
+		);
+
+		let audio = doc.get("Audio").unwrap();
+
+		assert_eq!(audio.len(), 1);
+		assert_eq!(audio.get("Volume").unwrap().value, KeyValue::Float(0.5f64));
+		assert!(doc.validate(&schema).is_ok());
+
+		let invalid = Schema::new(&[
+			SchemaEntry::new("Audio", "Muted", ValueType::Integer, false)
+				.with_default(KeyValue::Integer(0i64)),
+			SchemaEntry::new("Audio", "Balance", ValueType::Float, false)
+				.with_default(KeyValue::String(String::from("Centre"))),
+		]);
+
+		let err = match doc.fill_defaults(&invalid)
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(err.contains("Default of Audio.Balance expected Float but found String."));
+		assert!(!doc.get("Audio").unwrap().contains("Muted"));
+	}
+	#[test]
+	fn block_comment_test()
+	{
+		let mut lexer = Lexer::new();
+
+		match lexer
+			.parse_string("[Section] /* outer /* inner */ still\n commented */ Value = /**/ 3 / 2")
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let tokens: Vec<Token> = std::iter::from_fn(|| lexer.pop_front()).collect();
+
+		assert_eq!(
+			tokens,
+			vec![
+				Token::OpenBracket,
+				Token::Identifier(String::from("Section")),
+				Token::CloseBracket,
+				Token::Identifier(String::from("Value")),
+				Token::Equals,
+				Token::Integer(3),
+				Token::Divide,
+				Token::Integer(2),
+			]
+		);
+
+		let err = Lexer::new()
+			.parse_string("/* outer /* inner */ /* again /* deepest */")
+			.unwrap_err();
+
+		assert!(err.to_string().contains("2 left unclosed"));
+	}
+	#[test]
+	fn document_try_from_test()
+	{
+		let expected = Document::from_str(TEST_DOCUMENT).unwrap();
+
+		let from_str = Document::try_from(TEST_DOCUMENT).unwrap();
+		let from_string: Document = String::from(TEST_DOCUMENT).try_into().unwrap();
+
+		assert_eq!(from_str.to_string(), expected.to_string());
+		assert_eq!(from_string.to_string(), expected.to_string());
+
+		let path =
+			std::env::temp_dir().join(format!("parsecfg_try_from_test_{}.cfg", std::process::id()));
+
+		std::fs::write(&path, TEST_DOCUMENT).unwrap();
+
+		let from_path = Document::try_from(path.as_path());
+
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(from_path.unwrap().to_string(), expected.to_string());
+
+		assert!(Document::try_from("[Section] Key = ").is_err());
+		assert!(Document::try_from(String::from("[Section Key = 1")).is_err());
+		assert!(Document::try_from(path.as_path()).is_err());
+	}
+	#[test]
+	fn section_set_test()
+	{
+		let mut section = Section::new("Size", &[Key::new("Width", KeyValue::Integer(800))]);
+
+		assert_eq!(
+			section.set("width", KeyValue::Integer(1280)),
+			Some(KeyValue::Integer(800))
+		);
+		assert_eq!(section.len(), 1);
+		assert_eq!(section.get_at(0).unwrap().name(), "Width");
+		assert_eq!(section.get("Width").unwrap().value, KeyValue::Integer(1280));
+
+		assert_eq!(section.set("Height", KeyValue::Integer(720)), None);
+		assert_eq!(section.len(), 2);
+
+		let mut spaced = Section::new("Spaced", &[]);
+
+		assert_eq!(spaced.set("my key", KeyValue::Integer(1)), None);
+		assert_eq!(
+			spaced.set("my key", KeyValue::Integer(2)),
+			Some(KeyValue::Integer(1))
+		);
+		assert_eq!(spaced.len(), 1);
+		assert_eq!(spaced.get("my_key").unwrap().value, KeyValue::Integer(2));
+
+		let old = section.push_or_replace(Key::new("WIDTH", KeyValue::Integer(1920)));
+
+		assert_eq!(old, Some(Key::new("Width", KeyValue::Integer(1280))));
+		assert_eq!(section.len(), 2);
+		assert_eq!(section.get_at(0).unwrap().name(), "Width");
+		assert_eq!(section.get("Width").unwrap().value, KeyValue::Integer(1920));
+
+		assert_eq!(
+			section.push_or_replace(Key::new("Depth", KeyValue::Integer(32))),
+			None
+		);
+		assert_eq!(section.get_at(2).unwrap().name(), "Depth");
+	}
+	#[test]
+	fn path_test()
+	{
+		fn key(s: &str) -> PathSegment { PathSegment::Key(String::from(s)) }
+
+		assert_eq!(
+			parse_path("Section.Key").unwrap(),
+			vec![key("Section"), key("Key")]
+		);
+		assert_eq!(
+			parse_path("Section.Array[2].Table[0][1]").unwrap(),
+			vec![
+				key("Section"),
+				key("Array"),
+				PathSegment::Index(2),
+				key("Table"),
+				PathSegment::Index(0),
+				PathSegment::Index(1),
+			]
+		);
+		assert_eq!(parse_path("\"a.b\".c").unwrap(), vec![key("a.b"), key("c")]);
+		assert_eq!(
+			parse_path("Section.\"x[0]\"[3]").unwrap(),
+			vec![key("Section"), key("x[0]"), PathSegment::Index(3)]
+		);
+		assert_eq!(parse_path("[1]").unwrap(), vec![PathSegment::Index(1)]);
+		assert_eq!(
+			parse_path("\"a b\"[0]").unwrap(),
+			vec![key("a b"), PathSegment::Index(0)]
+		);
+
+		for bad in [
+			"",
+			"a..b",
+			"a.",
+			".a",
+			"a[",
+			"a[x]",
+			"a.[0]",
+			"\"a.b",
+			"a]b",
+			"\"\".a",
+			"a. b",
+			"a .b",
+			" a",
+			"a[ 1 ]",
+			"a[]",
+			"a[+1]",
+			"a\"b\"",
+			"\"a\"\"b\"",
+			"\"a\"b",
+		]
+		{
+			assert!(parse_path(bad).is_err(), "{bad}");
+		}
+	}
+	#[test]
+	fn merge_report_test()
+	{
+		let mut doc = Document::from_str(
+			"[Size]\nWidth = 800\nHeight = 600\n[Audio]\nVolume = 0.5\nMuted = 0\nTags = [\"a\"]",
+		)
+		.unwrap();
+		let other = Document::from_str(
+			"[size]\nwidth = 1280\nHeight = 600\n[Audio]\nVolume = 1.0\nMuted = \"no\"\nTags = \
+			 [\"b\"]\nDevice = \"hw:0\"",
+		)
+		.unwrap();
+		let policy = MergePolicy {
+			conflicts: ConflictPolicy::Error,
+			arrays: ArrayMergePolicy::Append,
+		};
+
+		let conflicts = match doc.merge_report(&other, &policy)
+		{
+			Ok(c) => c,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert_eq!(
+			conflicts,
+			vec![
+				MergeConflict {
+					section: String::from("Size"),
+					key: String::from("Width"),
+					existing: KeyValue::Integer(800),
+					incoming: KeyValue::Integer(1280),
+				},
+				MergeConflict {
+					section: String::from("Audio"),
+					key: String::from("Volume"),
+					existing: KeyValue::Float(0.5),
+					incoming: KeyValue::Float(1.0),
+				},
+				MergeConflict {
+					section: String::from("Audio"),
+					key: String::from("Muted"),
+					existing: KeyValue::Integer(0),
+					incoming: KeyValue::String(String::from("no")),
+				},
+			]
+		);
+
+		let audio = doc.get("Audio").unwrap();
+
+		assert_eq!(
+			doc.get("Size").unwrap().get("Width").unwrap().value,
+			KeyValue::Integer(800)
+		);
+		assert_eq!(audio.get("Volume").unwrap().value, KeyValue::Float(0.5));
+		assert_eq!(
+			audio.get("Tags").unwrap().value,
+			KeyValue::StringArray(vec![String::from("a"), String::from("b")])
+		);
+		assert_eq!(
+			audio.get("Device").unwrap().value,
+			KeyValue::String(String::from("hw:0"))
+		);
+	}
+	#[test]
+	fn numeric_range_test()
+	{
+		assert!(KeyValue::Integer(60).in_range(30.0, 144.0));
+		assert!(KeyValue::Integer(30).in_range(30.0, 144.0));
+		assert!(!KeyValue::Integer(-1).in_range(30.0, 144.0));
+		assert!(!KeyValue::Integer(240).in_range(30.0, 144.0));
+		assert!(KeyValue::Unsigned(144).in_range(30.0, 144.0));
+		assert!(!KeyValue::String(String::from("60")).in_range(30.0, 144.0));
+		assert!(!KeyValue::IntegerArray(vec![60]).in_range(30.0, 144.0));
+
+		let mut volume = KeyValue::Float(1.5);
+
+		volume.clamp_numeric(0.0, 1.0);
+		assert_eq!(volume, KeyValue::Float(1.0));
+
+		volume = KeyValue::Float(-0.25);
+		volume.clamp_numeric(0.0, 1.0);
+		assert_eq!(volume, KeyValue::Float(0.0));
+
+		let mut fps = KeyValue::Integer(10);
+
+		fps.clamp_numeric(29.5, 144.5);
+		assert_eq!(fps, KeyValue::Integer(30));
+
+		fps = KeyValue::Integer(200);
+		fps.clamp_numeric(29.5, 144.5);
+		assert_eq!(fps, KeyValue::Integer(144));
+
+		let mut name = KeyValue::String(String::from("a"));
+
+		name.clamp_numeric(0.0, 1.0);
+		assert_eq!(name, KeyValue::String(String::from("a")));
+
+		// Empty ranges leave the value unchanged.
+		fps.clamp_numeric(144.0, 30.0);
+		assert_eq!(fps, KeyValue::Integer(144));
+		volume.clamp_numeric(1.0, 0.5);
+		assert_eq!(volume, KeyValue::Float(0.0));
+		volume.clamp_numeric(f64::NAN, 1.0);
+		assert_eq!(volume, KeyValue::Float(0.0));
+		fps.clamp_numeric(0.0, f64::NAN);
+		assert_eq!(fps, KeyValue::Integer(144));
+
+		// Ranges without an integer only clamp floating point values.
+		fps.clamp_numeric(0.2, 0.8);
+		assert_eq!(fps, KeyValue::Integer(144));
+		volume.clamp_numeric(0.2, 0.8);
+		assert_eq!(volume, KeyValue::Float(0.2));
+
+		let mut count = KeyValue::Unsigned(5);
+
+		count.clamp_numeric(-10.0, -1.0);
+		assert_eq!(count, KeyValue::Unsigned(5));
+		count.clamp_numeric(-10.0, 2.0);
+		assert_eq!(count, KeyValue::Unsigned(2));
+	}
+	#[test]
+	fn snapshot_test()
+	{
+		let doc = Document::from_str("[b]\nZeta = 1\nalpha = 2\nBeta = 3\n[a]").unwrap();
+		let section = doc.get("b").unwrap();
+
+		let mut keys = section.keys();
+
+		assert_eq!(keys.len(), section.len());
+
+		keys.sort_by_key(|k| k.name().to_lowercase());
+
+		let names: Vec<&str> = keys.iter().map(|k| k.name().as_str()).collect();
+
+		assert_eq!(names, vec!["alpha", "Beta", "Zeta"]);
+		assert_eq!(section.get_at(0).unwrap().name(), "Zeta");
+
+		let mut sections = doc.sections();
+
+		sections.sort_by_key(|s| s.name().clone());
+		assert_eq!(sections[0].name(), "a");
+		assert_eq!(doc.get_at(0).unwrap().name(), "b");
+	}
+	#[test]
+	fn max_string_len_test()
+	{
+		let options = ParseOptions {
+			max_string_len: Some(8),
+			..Default::default()
+		};
+
+		let mut lexer = Lexer::with_options(options.clone());
+
+		match lexer.parse_string("[Section]\nName = \"abcd\" \"efgh\"")
+		{
+			Ok(_) =>
+			{}
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		let mut lexer = Lexer::with_options(options.clone());
+		let err = lexer
+			.parse_string("[Section]\nName = \"abcd\" \"efghi\"")
+			.unwrap_err()
+			.to_string();
+
+		assert!(err.contains("line 2, column 15"), "{err}");
+		assert!(err.contains("9 bytes"), "{err}");
+
+		// The overlong string is rejected before it is joined to the previous one.
+		while let Some(token) = lexer.pop_front()
+		{
+			if let Token::String(s) = token
+			{
+				assert_eq!(s, "abcd");
+			}
+		}
+
+		assert!(Document::from_str_with("[Section]\nName = \"abcdefghi\"", &options).is_err());
+		assert!(Document::from_str("[Section]\nName = \"abcdefghi\"").is_ok());
+	}
+	#[test]
+	fn check_invariants_test()
+	{
+		assert!(Document::from_str(TEST_DOCUMENT)
+			.unwrap()
+			.check_invariants()
+			.is_empty());
+
+		let table = KeyValue::Table(vec![
+			Key::new("Inner", KeyValue::Integer(1)),
+			Key::new("inner", KeyValue::Integer(2)),
+		]);
+		let doc = Document::new(&[
+			Section::new(
+				"Size",
+				&[
+					Key::new("Width", KeyValue::Integer(800)),
+					Key::new("WIDTH", KeyValue::Integer(1280)),
+					Key::new("Nested", KeyValue::Tuple(vec![KeyValue::Integer(0), table])),
+					Key::with_unchecked_name("", KeyValue::Integer(3)),
+					Key::with_unchecked_name("1 bad", KeyValue::Integer(4)),
+				],
+			),
+			Section::new("size", &[]),
+		]);
+
+		let problems = doc.check_invariants();
+
+		assert_eq!(
+			problems,
+			vec![
+				"Key Size.WIDTH is a duplicate.",
+				"Key Size.Nested[1].inner is a duplicate.",
+				"Key Size[3] has an empty name.",
+				"Key Size[4] has the invalid name 1 bad.",
+				"Section size is a duplicate.",
+			],
+		);
+	}
+	#[test]
+	fn key_comment_test()
+	{
+		let mut key =
+			Key::new("Width", KeyValue::Integer(800)).with_comment("Window width.\nIn pixels.");
+
+		assert_eq!(key.comment(), Some("Window width.\nIn pixels."));
+		assert_eq!(
+			key.to_string(),
+			"# Window width.\n# In pixels.\nWidth = 800"
+		);
+
+		let section = Section::new("Size", &[key.clone()]);
+		let doc = Document::from_str(&section.to_string()).unwrap();
+
+		assert_eq!(
+			doc.get("Size").unwrap().get("Width").unwrap().value,
+			KeyValue::Integer(800)
+		);
+
+		let options = ParseOptions {
+			parse_directives: true,
+			..Default::default()
+		};
+		let typed = Key::new("Name", KeyValue::String(String::from("Gary")))
+			.with_comment("Shown to players.\n  @type: string");
+
+		assert_eq!(
+			typed.to_string(),
+			"# Shown to players.\n#   \\@type: string\nName = \"Gary\""
+		);
+
+		let doc = Document::from_str_with(&Section::new("Player", &[typed]).to_string(), &options)
+			.unwrap();
+
+		assert!(doc
+			.get("Player")
+			.unwrap()
+			.get("Name")
+			.unwrap()
+			.metadata()
+			.is_empty());
+
+		key.set_comment(None);
+		assert_eq!(key.comment(), None);
+		assert_eq!(key.to_string(), "Width = 800");
+	}
+	#[test]
+	fn minify_test()
+	{
+		let mut doc = match Document::from_str(TEST_DOCUMENT)
+		{
+			Ok(d) => d,
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		doc.push(Section::new(
+			"Extra",
+			&[
+				Key::new("Negative", KeyValue::Float(-2.0)).with_comment("Dropped."),
+				Key::new("Count", KeyValue::Unsigned(3)),
+				Key::new("Empty", KeyValue::IntegerArray(vec![])),
+				Key::new("Values", KeyValue::FloatArray(vec![1.0, -0.5])),
+				Key::new(
+					"Nested",
+					KeyValue::Tuple(vec![
+						KeyValue::Tuple(vec![]),
+						KeyValue::Table(vec![
+							Key::new("a", KeyValue::Integer(-1)),
+							Key::new("a", KeyValue::StringArray(vec![])),
+						]),
+					]),
+				),
+			],
+		));
+
+		let minified = doc.minify().unwrap();
+
+		assert!(!minified.contains('#'));
+		assert!(!minified.contains(' '));
+		assert!(
+			minified.starts_with("[Size]Width=800u\nHeight=600u[Position]X=20\nY=40[Extra]"),
+			"{minified}"
+		);
+		assert!(minified.ends_with("Nested=((),{a=-1,a=[]})"), "{minified}");
+
+		// Comments are not written, so compare without them.
+		doc.get_mut("Extra")
+			.unwrap()
+			.get_mut("Negative")
+			.unwrap()
+			.set_comment(None);
+
+		match Document::from_str(&minified)
+		{
+			Ok(d) => assert_eq!(d, doc),
+			Err(e) =>
+			{
+				println!("{e}");
+				panic!()
+			}
+		};
+
+		assert!(Document::new(&[]).minify().is_err());
+		assert!(Document::from_str("").is_err());
+
+		doc.get_mut("Extra")
+			.unwrap()
+			.set("Ratio", KeyValue::Float(f64::INFINITY));
+
+		let err = match doc.minify()
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(err.contains("Extra"), "{err}");
+		assert!(err.contains("Ratio"), "{err}");
+
+		doc.get_mut("Extra").unwrap().set(
+			"Ratio",
+			KeyValue::Tuple(vec![KeyValue::FloatArray(vec![1.0, f64::NAN])]),
+		);
+		assert!(doc.minify().is_err());
+	}
+	#[test]
+	fn error_excerpt_test()
+	{
+		let text = "[Size]\nWidth = 800\nLimits = [1, 2, \"three\", 4]\n";
+
+		let err = match Document::from_str(text)
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(err.contains("line 3, column 17"), "{err}");
+		assert!(
+			err.ends_with("Limits = [1, 2, \"three\", 4]\n                ^^^^^^^"),
+			"{err}"
+		);
+
+		let err = match Document::from_str("[Size]\n\tScale = f[1, \"2\"]")
+		{
+			Ok(_) => panic!(),
+			Err(e) => e.to_string(),
+		};
+
+		assert!(
+			err.ends_with("\tScale = f[1, \"2\"]\n\t             ^^^"),
+			"{err}"
+		);
+
+		let mut lexer = Lexer::new();
+
+		lexer.parse_string("Key = 1").unwrap();
+		assert!(lexer.excerpt().is_none());
+		lexer.pop_front();
+		assert_eq!(lexer.excerpt().unwrap(), "line 1, column 1:\nKey = 1\n^^^");
+
+		// Lines are counted from the start of each parsed string.
+		let mut reused = Lexer::new();
+
+		reused
+			.parse_string("Other = 2\nMore = 3\nLast = 4")
+			.unwrap();
+		reused.parse_string("Bad = [1, \"x\"]").unwrap();
+
+		for _ in 0..3
+		{
+			Key::from_lexer(&mut reused).unwrap();
+		}
+
+		let err = Key::from_lexer(&mut reused).unwrap_err().to_string();
+
+		assert!(
+			err.ends_with("line 1, column 11:\nBad = [1, \"x\"]\n          ^^^"),
+			"{err}"
+		);
+
+		let mut cached = Lexer::from_bytes(&lexer.to_bytes()).unwrap();
+
+		cached.pop_front();
+		assert!(cached.excerpt().is_none());
+	}
 }
@@ -25,6 +25,7 @@ pub enum Token
 {
 	Identifier(String),
 	String(String),
+	Directive(String, String), // # @name: value
 	Integer(i64),
 	Unsigned(u64),
 	Float(f64),
@@ -50,6 +51,7 @@ impl Display for Token
 		{
 			Token::Identifier(s) => write!(f, "{s}"),
 			Token::String(s) => write!(f, "\"{s}\""),
+			Token::Directive(n, v) => write!(f, "{COMMENT_CHAR} @{n}: {v}"),
 			Token::Integer(s) => write!(f, "{s}"),
 			Token::Unsigned(s) => write!(f, "{s}"),
 			Token::Float(s) => write!(f, "{s}"),